@@ -0,0 +1,13 @@
+use core::time::Duration;
+
+/// Angle ticks per revolution (14-bit).
+pub const RESOLUTION: u16 = 16384;
+
+/// Largest value of a register.
+pub const DATA_MASK: u16 = 0x3FFF;
+
+/// Time to power up the sensor.
+pub const POWER_UP_TIME: Duration = Duration::from_millis(10);
+
+/// Minimum time the chip select has to stay high between two frames.
+pub const CS_HIGH_TIME: Duration = Duration::from_nanos(350);
@@ -0,0 +1,96 @@
+/// Error flags of the `ERRFL` register.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ErrorFlags {
+    /// A frame was not 16 bits long.
+    pub framing: bool,
+    /// Read or write of an invalid register address.
+    pub invalid_command: bool,
+    /// A frame sent to the sensor had a wrong parity.
+    pub parity: bool,
+}
+
+impl From<u16> for ErrorFlags {
+    fn from(bits: u16) -> Self {
+        Self {
+            framing: bits & 0b001 != 0,
+            invalid_command: bits & 0b010 != 0,
+            parity: bits & 0b100 != 0,
+        }
+    }
+}
+
+impl From<ErrorFlags> for u16 {
+    fn from(flags: ErrorFlags) -> Self {
+        flags.framing as u16 | (flags.invalid_command as u16) << 1 | (flags.parity as u16) << 2
+    }
+}
+
+/// Diagnostics and automatic gain control, `DIAAGC` on the AS5047P.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Diagnostics {
+    /// Automatic gain control value, 0 for a strong field and 255 for a weak one.
+    pub agc: u8,
+    /// Offset compensation finished, readings are valid.
+    pub offset_ready: bool,
+    /// CORDIC overflow, the angle and magnitude are not valid.
+    pub cordic_overflow: bool,
+    /// Field too strong, magnet too close.
+    pub magnet_high: bool,
+    /// Field too weak, magnet too far.
+    pub magnet_low: bool,
+}
+
+impl Diagnostics {
+    /// Whether the angle can be trusted.
+    pub fn is_valid(&self) -> bool {
+        self.offset_ready && !self.cordic_overflow
+    }
+}
+
+impl From<u16> for Diagnostics {
+    fn from(bits: u16) -> Self {
+        Self {
+            agc: (bits & 0xFF) as u8,
+            offset_ready: bits & (1 << 8) != 0,
+            cordic_overflow: bits & (1 << 9) != 0,
+            magnet_high: bits & (1 << 10) != 0,
+            magnet_low: bits & (1 << 11) != 0,
+        }
+    }
+}
+
+impl From<Diagnostics> for u16 {
+    fn from(diagnostics: Diagnostics) -> Self {
+        diagnostics.agc as u16
+            | (diagnostics.offset_ready as u16) << 8
+            | (diagnostics.cordic_overflow as u16) << 9
+            | (diagnostics.magnet_high as u16) << 10
+            | (diagnostics.magnet_low as u16) << 11
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Diagnostics, ErrorFlags};
+
+    #[test]
+    fn flags_roundtrip() {
+        for bits in 0..0b1000 {
+            assert_eq!(u16::from(ErrorFlags::from(bits)), bits);
+        }
+    }
+
+    #[test]
+    fn diagnostics_roundtrip() {
+        for bits in [0x0000, 0x0180, 0x08FF, 0x0400, 0x0F5A] {
+            assert_eq!(u16::from(Diagnostics::from(bits)), bits);
+        }
+    }
+
+    #[test]
+    fn diagnostics_validity() {
+        assert!(Diagnostics::from(0x0180).is_valid());
+        assert!(!Diagnostics::from(0x0080).is_valid());
+        assert!(!Diagnostics::from(0x0380).is_valid());
+    }
+}
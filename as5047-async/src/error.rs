@@ -0,0 +1,31 @@
+use crate::diagnostics::ErrorFlags;
+
+/// Crate errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error<E> {
+    /// `SPI` communication error.
+    Communication(E),
+
+    /// Parity of a frame sent by the sensor does not match its content.
+    Parity(u16),
+
+    /// The sensor flagged an error on the previous command, flags are read and cleared.
+    Sensor(ErrorFlags),
+
+    /// A register read back after a write holds another value than written.
+    Verify {
+        /// Value written.
+        written: u16,
+        /// Value read back.
+        read: u16,
+    },
+
+    /// Register only available on the AS5047P.
+    Unsupported,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Self::Communication(e)
+    }
+}
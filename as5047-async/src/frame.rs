@@ -0,0 +1,103 @@
+use crate::constants::DATA_MASK;
+
+const PARITY: u16 = 0x8000;
+const READ: u16 = 0x4000;
+const ERROR_FLAG: u16 = 0x4000;
+
+/// Set the parity bit so the frame has an even number of ones.
+pub const fn with_parity(frame: u16) -> u16 {
+    let frame = frame & !PARITY;
+    if frame.count_ones() & 1 == 1 {
+        frame | PARITY
+    } else {
+        frame
+    }
+}
+
+/// Whether the frame has an even number of ones.
+pub const fn parity_ok(frame: u16) -> bool {
+    frame.count_ones() & 1 == 0
+}
+
+/// Command frame reading `address`.
+pub const fn read_command(address: u16) -> u16 {
+    with_parity(READ | (address & DATA_MASK))
+}
+
+/// Command frame writing `address`.
+pub const fn write_command(address: u16) -> u16 {
+    with_parity(address & DATA_MASK)
+}
+
+/// Data frame holding `data` for a write.
+pub const fn data_frame(data: u16) -> u16 {
+    with_parity(data & DATA_MASK)
+}
+
+/// Frame read from the sensor.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Response {
+    /// Register content.
+    pub data: u16,
+    /// The sensor flagged an error on the previous command.
+    pub error: bool,
+}
+
+impl TryFrom<u16> for Response {
+    type Error = u16;
+
+    /// Parse a frame, returns the frame itself if its parity is wrong.
+    fn try_from(frame: u16) -> Result<Self, Self::Error> {
+        if !parity_ok(frame) {
+            return Err(frame);
+        }
+        Ok(Self {
+            data: frame & DATA_MASK,
+            error: frame & ERROR_FLAG != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{data_frame, parity_ok, read_command, write_command, Response};
+
+    #[test]
+    fn commands_match_datasheet() {
+        // NOP, ANGLECOM and ERRFL reads from the AS5047P datasheet
+        assert_eq!(read_command(0x0000), 0xC000);
+        assert_eq!(read_command(0x3FFF), 0xFFFF);
+        assert_eq!(read_command(0x0001), 0x4001);
+        assert_eq!(write_command(0x0018), 0x0018);
+        assert_eq!(write_command(0x0019), 0x8019);
+        assert_eq!(data_frame(0x0004), 0x8004);
+    }
+
+    #[test]
+    fn every_frame_has_even_parity() {
+        for frame in 0..=0x3FFF {
+            assert!(parity_ok(read_command(frame)));
+            assert!(parity_ok(write_command(frame)));
+            assert!(parity_ok(data_frame(frame)));
+        }
+    }
+
+    #[test]
+    fn parses_responses() {
+        assert_eq!(
+            Response::try_from(0x9234),
+            Ok(Response {
+                data: 0x1234,
+                error: false
+            })
+        );
+        assert_eq!(Response::try_from(0x4000), Err(0x4000));
+        assert_eq!(
+            Response::try_from(0xC000),
+            Ok(Response {
+                data: 0,
+                error: true
+            })
+        );
+    }
+}
@@ -0,0 +1,176 @@
+#![cfg_attr(not(test), no_std)]
+
+use diagnostics::{Diagnostics, ErrorFlags};
+use embedded_hal_async::spi::SpiDevice;
+use error::Error;
+use frame::Response;
+use register::Register;
+use settings::{Settings1, Settings2};
+
+/// Constants.
+pub mod constants;
+/// Diagnostics and error flags.
+pub mod diagnostics;
+/// Errors.
+pub mod error;
+/// SPI frame encoding.
+pub mod frame;
+/// Registers.
+pub(crate) mod register;
+/// AS5047P settings.
+pub mod settings;
+#[cfg(test)]
+mod test_reading;
+#[cfg(test)]
+mod test_writing;
+
+// Bits 7:6 of ZPOSL enable the AS5047P magnet error compensation
+const ZPOSL_MASK: u16 = 0x003F;
+
+/// Supported sensors, both share the frame format but not every register.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Variant {
+    /// AS5047P, with dynamic angle error compensation and incremental outputs.
+    As5047P,
+    /// AS5048A.
+    As5048A,
+}
+
+pub struct As5047<SPI> {
+    variant: Variant,
+    spi: SPI,
+}
+
+impl<E, SPI: SpiDevice<Error = E>> As5047<SPI> {
+    /// Create a new AS5047P driver instance.
+    pub fn new(spi: SPI) -> Self {
+        Self::with_variant(Variant::As5047P, spi)
+    }
+
+    /// Create a new driver instance for the given sensor.
+    pub fn with_variant(variant: Variant, spi: SPI) -> Self {
+        Self { variant, spi }
+    }
+
+    /// Release the bus, consuming the driver.
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Sensor this driver talks to.
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Get the angle, with dynamic angle error compensation on the AS5047P.
+    pub async fn angle(&mut self) -> Result<u16, Error<E>> {
+        self.read(Register::Angle).await
+    }
+
+    /// Get the angle without dynamic angle error compensation.
+    pub async fn raw_angle(&mut self) -> Result<u16, Error<E>> {
+        self.read(Register::AngleUncompensated).await
+    }
+
+    /// Get the CORDIC magnitude of the field.
+    pub async fn magnitude(&mut self) -> Result<u16, Error<E>> {
+        self.read(Register::Magnitude).await
+    }
+
+    /// Get the diagnostics and automatic gain control.
+    pub async fn diagnostics(&mut self) -> Result<Diagnostics, Error<E>> {
+        Ok(self.read(Register::Diagnostics).await?.into())
+    }
+
+    /// Get and clear the error flags.
+    pub async fn error_flags(&mut self) -> Result<ErrorFlags, Error<E>> {
+        self.read(Register::Errfl).await.map(ErrorFlags::from)
+    }
+
+    /// Get the zero position.
+    pub async fn zero_position(&mut self) -> Result<u16, Error<E>> {
+        let msb = self.read(Register::Zposm).await?;
+        let lsb = self.read(Register::Zposl).await?;
+        Ok((msb & 0xFF) << 6 | lsb & ZPOSL_MASK)
+    }
+
+    /// Set the zero position, until the next power cycle.
+    pub async fn set_zero_position(&mut self, position: u16) -> Result<(), Error<E>> {
+        let lsb = self.read(Register::Zposl).await?;
+        self.write(Register::Zposm, (position >> 6) & 0xFF).await?;
+        self.write(Register::Zposl, lsb & !ZPOSL_MASK | position & ZPOSL_MASK)
+            .await
+    }
+
+    /// Get the AS5047P settings 1.
+    pub async fn settings1(&mut self) -> Result<Settings1, Error<E>> {
+        Ok(self.read(Register::Settings1).await?.into())
+    }
+
+    /// Set the AS5047P settings 1, until the next power cycle.
+    pub async fn set_settings1(&mut self, settings: Settings1) -> Result<(), Error<E>> {
+        self.write(Register::Settings1, settings.into()).await
+    }
+
+    /// Get the AS5047P settings 2.
+    pub async fn settings2(&mut self) -> Result<Settings2, Error<E>> {
+        Ok(self.read(Register::Settings2).await?.into())
+    }
+
+    /// Set the AS5047P settings 2, until the next power cycle.
+    pub async fn set_settings2(&mut self, settings: Settings2) -> Result<(), Error<E>> {
+        self.write(Register::Settings2, settings.into()).await
+    }
+
+    /// Helper function reading a register, the content comes with the frame following the command.
+    async fn read(&mut self, register: Register) -> Result<u16, Error<E>> {
+        let address = register.address(self.variant).ok_or(Error::Unsupported)?;
+        self.transfer(frame::read_command(address)).await?;
+        let response = self.response().await?;
+        if response.error {
+            let flags = self.response_flags().await?;
+            return Err(Error::Sensor(flags));
+        }
+        Ok(response.data)
+    }
+
+    /// Helper function writing a register, the new content is read back to check the write.
+    async fn write(&mut self, register: Register, data: u16) -> Result<(), Error<E>> {
+        let address = register.address(self.variant).ok_or(Error::Unsupported)?;
+        self.transfer(frame::write_command(address)).await?;
+        self.transfer(frame::data_frame(data)).await?;
+        let response = self.response().await?;
+        if response.error {
+            let flags = self.response_flags().await?;
+            return Err(Error::Sensor(flags));
+        }
+        if response.data != data {
+            return Err(Error::Verify {
+                written: data,
+                read: response.data,
+            });
+        }
+        Ok(())
+    }
+
+    /// Helper function fetching the result of the previous command with a `NOP`.
+    async fn response(&mut self) -> Result<Response, Error<E>> {
+        let nop = Register::Nop.address(self.variant).unwrap_or_default();
+        let frame = self.transfer(frame::read_command(nop)).await?;
+        Response::try_from(frame).map_err(Error::Parity)
+    }
+
+    /// Helper function reading and clearing the error flags after a flagged response.
+    async fn response_flags(&mut self) -> Result<ErrorFlags, Error<E>> {
+        let address = Register::Errfl.address(self.variant).unwrap_or_default();
+        self.transfer(frame::read_command(address)).await?;
+        Ok(ErrorFlags::from(self.response().await?.data))
+    }
+
+    /// Helper function exchanging one frame, each frame is its own chip select cycle.
+    async fn transfer(&mut self, frame: u16) -> Result<u16, Error<E>> {
+        let mut buffer = frame.to_be_bytes();
+        self.spi.transfer_in_place(&mut buffer).await?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+}
@@ -0,0 +1,48 @@
+use crate::Variant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// No operation, reading it fetches the result of the previous command.
+    Nop,
+    /// Error flags, cleared by reading them.
+    Errfl,
+    /// Zero position, 8 most significant bits.
+    Zposm,
+    /// Zero position, 6 least significant bits and AS5047P error compensation enables.
+    Zposl,
+    /// AS5047P settings 1.
+    Settings1,
+    /// AS5047P settings 2.
+    Settings2,
+    /// Diagnostics and automatic gain control.
+    Diagnostics,
+    /// CORDIC magnitude.
+    Magnitude,
+    /// Angle without dynamic angle error compensation.
+    AngleUncompensated,
+    /// Angle with dynamic angle error compensation on the AS5047P.
+    Angle,
+}
+
+impl Register {
+    /// Address of the register, `None` if the variant does not have it.
+    pub fn address(self, variant: Variant) -> Option<u16> {
+        let address = match (self, variant) {
+            (Self::Nop, _) => 0x0000,
+            (Self::Errfl, _) => 0x0001,
+            (Self::Zposm, _) => 0x0016,
+            (Self::Zposl, _) => 0x0017,
+            (Self::Settings1, Variant::As5047P) => 0x0018,
+            (Self::Settings2, Variant::As5047P) => 0x0019,
+            (Self::Settings1 | Self::Settings2, Variant::As5048A) => return None,
+            (Self::Diagnostics, Variant::As5047P) => 0x3FFC,
+            (Self::Diagnostics, Variant::As5048A) => 0x3FFD,
+            (Self::Magnitude, Variant::As5047P) => 0x3FFD,
+            (Self::Magnitude, Variant::As5048A) => 0x3FFE,
+            (Self::AngleUncompensated, Variant::As5047P) => 0x3FFE,
+            // No compensation on the AS5048A, both angles are the same register
+            (Self::AngleUncompensated | Self::Angle, _) => 0x3FFF,
+        };
+        Some(address)
+    }
+}
@@ -0,0 +1,162 @@
+/// Rotation direction counted as positive.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u8)]
+pub enum Direction {
+    /// Angle increases clockwise, looking at the top of the chip.
+    Clockwise = 0,
+    /// Angle increases counter-clockwise, looking at the top of the chip.
+    CounterClockwise = 1,
+}
+
+/// Incremental interface on the ABI and UVW pins.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u8)]
+pub enum IncrementalOutput {
+    /// Quadrature A, B and index on the ABI pins, PWM on W.
+    Abi = 0,
+    /// Commutation signals on the UVW pins, PWM on I.
+    Uvw = 1,
+}
+
+/// Angle found at the `ANGLECOM` address.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u8)]
+pub enum DataSelect {
+    /// Angle with dynamic angle error compensation.
+    Compensated = 0,
+    /// Angle straight from the CORDIC.
+    Uncompensated = 1,
+}
+
+/// AS5047P `SETTINGS1` register.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Settings1 {
+    /// Rotation direction counted as positive.
+    pub direction: Direction,
+    /// Incremental interface on the output pins.
+    pub output: IncrementalOutput,
+    /// Dynamic angle error compensation disabled.
+    pub daec_disabled: bool,
+    /// ABI resolution in powers of two instead of decimal steps.
+    pub abi_binary: bool,
+    /// Angle found at the `ANGLECOM` address.
+    pub data_select: DataSelect,
+    /// PWM output enabled.
+    pub pwm: bool,
+}
+
+impl Default for Settings1 {
+    /// Factory settings.
+    fn default() -> Self {
+        Self {
+            direction: Direction::Clockwise,
+            output: IncrementalOutput::Abi,
+            daec_disabled: false,
+            abi_binary: false,
+            data_select: DataSelect::Compensated,
+            pwm: false,
+        }
+    }
+}
+
+impl From<u16> for Settings1 {
+    fn from(bits: u16) -> Self {
+        let bit = |n: u16| bits & (1 << n) != 0;
+        Self {
+            direction: if bit(2) {
+                Direction::CounterClockwise
+            } else {
+                Direction::Clockwise
+            },
+            output: if bit(3) {
+                IncrementalOutput::Uvw
+            } else {
+                IncrementalOutput::Abi
+            },
+            daec_disabled: bit(4),
+            abi_binary: bit(5),
+            data_select: if bit(6) {
+                DataSelect::Uncompensated
+            } else {
+                DataSelect::Compensated
+            },
+            pwm: bit(7),
+        }
+    }
+}
+
+impl From<Settings1> for u16 {
+    fn from(settings: Settings1) -> Self {
+        // Bit 0 is a factory setting that always reads 1
+        0b1 | (settings.direction as u16) << 2
+            | (settings.output as u16) << 3
+            | (settings.daec_disabled as u16) << 4
+            | (settings.abi_binary as u16) << 5
+            | (settings.data_select as u16) << 6
+            | (settings.pwm as u16) << 7
+    }
+}
+
+/// AS5047P `SETTINGS2` register.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct Settings2 {
+    /// UVW pole pairs, 1 to 7.
+    pub uvw_pole_pairs: u8,
+    /// Hysteresis code of the incremental outputs, 0 to 3, see the datasheet for its meaning.
+    pub hysteresis: u8,
+    /// ABI resolution code, 0 to 7, see the datasheet for the steps per revolution it stands for.
+    pub abi_resolution: u8,
+}
+
+impl From<u16> for Settings2 {
+    fn from(bits: u16) -> Self {
+        Self {
+            // Codes 0b110 and 0b111 both stand for 7 pole pairs
+            uvw_pole_pairs: ((bits & 0b111) as u8 + 1).min(7),
+            hysteresis: ((bits >> 3) & 0b11) as u8,
+            abi_resolution: ((bits >> 5) & 0b111) as u8,
+        }
+    }
+}
+
+impl From<Settings2> for u16 {
+    fn from(settings: Settings2) -> Self {
+        (settings.uvw_pole_pairs.clamp(1, 7) as u16 - 1)
+            | ((settings.hysteresis & 0b11) as u16) << 3
+            | ((settings.abi_resolution & 0b111) as u16) << 5
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DataSelect, Direction, IncrementalOutput, Settings1, Settings2};
+
+    #[test]
+    fn settings1_roundtrip() {
+        for bits in (0..0x100).filter(|bits| bits & 0b11 == 0b01) {
+            assert_eq!(u16::from(Settings1::from(bits)), bits);
+        }
+        assert_eq!(u16::from(Settings1::default()), 0x0001);
+    }
+
+    #[test]
+    fn settings1_fields() {
+        let settings = Settings1 {
+            direction: Direction::CounterClockwise,
+            output: IncrementalOutput::Uvw,
+            daec_disabled: true,
+            data_select: DataSelect::Uncompensated,
+            ..Default::default()
+        };
+        assert_eq!(u16::from(settings), 0b0101_1101);
+    }
+
+    #[test]
+    fn settings2_roundtrip() {
+        for bits in (0..0x100).filter(|bits| bits & 0b111 != 0b111) {
+            assert_eq!(u16::from(Settings2::from(bits)), bits);
+        }
+        assert_eq!(Settings2::from(0).uvw_pole_pairs, 1);
+        assert_eq!(Settings2::from(0b111).uvw_pole_pairs, 7);
+    }
+}
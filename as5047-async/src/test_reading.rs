@@ -0,0 +1,166 @@
+use crate::{
+    diagnostics::{Diagnostics, ErrorFlags},
+    error::Error,
+    settings::{DataSelect, Direction, IncrementalOutput, Settings1, Settings2},
+    As5047, Variant,
+};
+use embassy_futures::block_on;
+use embedded_hal_mock::eh1::spi::{Mock, Transaction};
+
+/// One frame, in its own chip select cycle.
+pub fn frame(write: u16, read: u16) -> Vec<Transaction<u8>> {
+    vec![
+        Transaction::transaction_start(),
+        Transaction::transfer_in_place(write.to_be_bytes().to_vec(), read.to_be_bytes().to_vec()),
+        Transaction::transaction_end(),
+    ]
+}
+
+pub fn mock(frames: &[(u16, u16)]) -> Mock<u8> {
+    let transactions: Vec<_> = frames
+        .iter()
+        .flat_map(|(write, read)| frame(*write, *read))
+        .collect();
+    Mock::new(&transactions)
+}
+
+#[test]
+fn reads_compensated_angle() {
+    // ANGLECOM command, then a NOP returning 0x1234
+    let spi = mock(&[(0xFFFF, 0x0000), (0xC000, 0x9234)]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(block_on(as5047.angle()), Ok(0x1234));
+    as5047.release().done();
+}
+
+#[test]
+fn reads_uncompensated_angle() {
+    let spi = mock(&[(0x7FFE, 0x0000), (0xC000, 0x3FFF)]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(block_on(as5047.raw_angle()), Ok(0x3FFF));
+    as5047.release().done();
+}
+
+#[test]
+fn as5048a_has_a_single_angle() {
+    let spi = mock(&[
+        (0xFFFF, 0x0000),
+        (0xC000, 0x0001 | 0x8000),
+        (0xFFFF, 0x0000),
+        (0xC000, 0x0002 | 0x8000),
+    ]);
+    let mut as5048 = As5047::with_variant(Variant::As5048A, spi);
+    assert_eq!(block_on(as5048.angle()), Ok(1));
+    assert_eq!(block_on(as5048.raw_angle()), Ok(2));
+    as5048.release().done();
+}
+
+#[test]
+fn rejects_bad_parity() {
+    let spi = mock(&[(0xFFFF, 0x0000), (0xC000, 0x1234)]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(block_on(as5047.angle()), Err(Error::Parity(0x1234)));
+    as5047.release().done();
+}
+
+#[test]
+fn reports_and_clears_error_flags() {
+    // Error flag set on the response, ERRFL then reads a parity error
+    let spi = mock(&[
+        (0xFFFF, 0x0000),
+        (0xC000, 0xC000),
+        (0x4001, 0x0000),
+        (0xC000, 0x8004),
+    ]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(
+        block_on(as5047.angle()),
+        Err(Error::Sensor(ErrorFlags {
+            parity: true,
+            ..Default::default()
+        }))
+    );
+    as5047.release().done();
+}
+
+#[test]
+fn reads_diagnostics_and_magnitude() {
+    let spi = mock(&[
+        (0xFFFC, 0x0000),
+        (0xC000, 0x8980),
+        (0x7FFD, 0x0000),
+        (0xC000, 0x0FFF),
+    ]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(
+        block_on(as5047.diagnostics()),
+        Ok(Diagnostics {
+            agc: 0x80,
+            offset_ready: true,
+            magnet_low: true,
+            ..Default::default()
+        })
+    );
+    assert_eq!(block_on(as5047.magnitude()), Ok(0x0FFF));
+    as5047.release().done();
+}
+
+#[test]
+fn as5048a_diagnostics_address() {
+    let spi = mock(&[(0x7FFD, 0x0000), (0xC000, 0x0000)]);
+    let mut as5048 = As5047::with_variant(Variant::As5048A, spi);
+    assert_eq!(block_on(as5048.diagnostics()), Ok(Diagnostics::default()));
+    as5048.release().done();
+}
+
+#[test]
+fn reads_zero_position() {
+    // ZPOSM holds bits 13:6 and ZPOSL bits 5:0, above them are the compensation enables
+    let spi = mock(&[
+        (0x4016, 0x0000),
+        (0xC000, 0x80AB),
+        (0xC017, 0x0000),
+        (0xC000, 0x80CD),
+    ]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(block_on(as5047.zero_position()), Ok(0xAB << 6 | 0x0D));
+    as5047.release().done();
+}
+
+#[test]
+fn reads_settings() {
+    let spi = mock(&[
+        (0xC018, 0x0000),
+        (0xC000, 0x805D),
+        (0x4019, 0x0000),
+        (0xC000, 0x0082),
+    ]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(
+        block_on(as5047.settings1()),
+        Ok(Settings1 {
+            direction: Direction::CounterClockwise,
+            output: IncrementalOutput::Uvw,
+            daec_disabled: true,
+            data_select: DataSelect::Uncompensated,
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        block_on(as5047.settings2()),
+        Ok(Settings2 {
+            uvw_pole_pairs: 3,
+            hysteresis: 0,
+            abi_resolution: 4,
+        })
+    );
+    as5047.release().done();
+}
+
+#[test]
+fn as5048a_has_no_settings() {
+    let spi = mock(&[]);
+    let mut as5048 = As5047::with_variant(Variant::As5048A, spi);
+    assert_eq!(block_on(as5048.settings1()), Err(Error::Unsupported));
+    as5048.release().done();
+}
@@ -0,0 +1,78 @@
+use crate::{
+    error::Error,
+    settings::{DataSelect, Direction, IncrementalOutput, Settings1, Settings2},
+    test_reading::mock,
+    As5047, Variant,
+};
+use embassy_futures::block_on;
+
+#[test]
+fn writes_settings() {
+    // Command, data, then a NOP reading back the new content
+    let spi = mock(&[
+        (0x0018, 0x0000),
+        (0x805D, 0x0001),
+        (0xC000, 0x805D),
+        (0x8019, 0x0000),
+        (0x0082, 0x0000),
+        (0xC000, 0x0082),
+    ]);
+    let mut as5047 = As5047::new(spi);
+    let settings1 = Settings1 {
+        direction: Direction::CounterClockwise,
+        output: IncrementalOutput::Uvw,
+        daec_disabled: true,
+        data_select: DataSelect::Uncompensated,
+        ..Default::default()
+    };
+    assert_eq!(block_on(as5047.set_settings1(settings1)), Ok(()));
+    let settings2 = Settings2 {
+        uvw_pole_pairs: 3,
+        hysteresis: 0,
+        abi_resolution: 4,
+    };
+    assert_eq!(block_on(as5047.set_settings2(settings2)), Ok(()));
+    as5047.release().done();
+}
+
+#[test]
+fn writes_zero_position_keeping_compensation_enables() {
+    let spi = mock(&[
+        (0xC017, 0x0000),
+        (0xC000, 0x00C0),
+        (0x8016, 0x0000),
+        (0x0048, 0x0000),
+        (0xC000, 0x0048),
+        (0x0017, 0x0000),
+        (0x80F4, 0x00C0),
+        (0xC000, 0x80F4),
+    ]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(block_on(as5047.set_zero_position(0x1234)), Ok(()));
+    as5047.release().done();
+}
+
+#[test]
+fn detects_failed_writes() {
+    let spi = mock(&[(0x0018, 0x0000), (0x8001, 0x0000), (0xC000, 0x805D)]);
+    let mut as5047 = As5047::new(spi);
+    assert_eq!(
+        block_on(as5047.set_settings1(Settings1::default())),
+        Err(Error::Verify {
+            written: 0x0001,
+            read: 0x005D
+        })
+    );
+    as5047.release().done();
+}
+
+#[test]
+fn as5048a_has_no_settings() {
+    let spi = mock(&[]);
+    let mut as5048 = As5047::with_variant(Variant::As5048A, spi);
+    assert_eq!(
+        block_on(as5048.set_settings2(Settings2::default())),
+        Err(Error::Unsupported)
+    );
+    as5048.release().done();
+}
@@ -3,6 +3,9 @@ use core::time::Duration;
 /// Default i2c address of AS5600.
 pub const DEFAULT_I2C_ADDRESS: u8 = 0x36;
 
+/// Angle ticks per revolution (12-bit).
+pub const RESOLUTION: u16 = 4096;
+
 /// Watchdog timeout duration (before it changes power modes).
 pub const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(60);
 /// Time to power up AS5600.
@@ -1,4 +1,4 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 use configuration::Configuration;
 use constants::DEFAULT_I2C_ADDRESS;
@@ -14,6 +14,8 @@ pub mod configuration;
 pub mod constants;
 /// Errors.
 pub mod error;
+/// Multi-turn position tracking.
+pub mod multi_turn;
 /// Registers.
 pub(crate) mod register;
 /// Magnet detection status.
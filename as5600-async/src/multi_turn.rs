@@ -0,0 +1,207 @@
+use core::time::Duration;
+
+use crate::constants::RESOLUTION;
+
+/// Multi-turn tracking errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The angle moved further between two samples than the maximum velocity allows, or the
+    /// maximum velocity allows for half a revolution or more, so the turn count may be off.
+    /// The tracker still follows the shortest path between the two angles.
+    Ambiguous {
+        /// Shortest path between the two samples, in ticks.
+        delta: i32,
+        /// Largest distance the maximum velocity allows over the elapsed time, in ticks.
+        limit: u32,
+    },
+
+    /// Angle is not below the tracker resolution.
+    OutOfRange(u16),
+}
+
+/// Accumulates single-turn angles into a continuous, signed position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiTurnTracker {
+    resolution: u16,
+    // Ticks per second
+    max_velocity: Option<u32>,
+    last_angle: Option<u16>,
+    position: i64,
+}
+
+impl Default for MultiTurnTracker {
+    fn default() -> Self {
+        Self::new(RESOLUTION)
+    }
+}
+
+impl MultiTurnTracker {
+    /// Create a new tracker for angles of `resolution` ticks per revolution.
+    pub const fn new(resolution: u16) -> Self {
+        Self {
+            resolution,
+            max_velocity: None,
+            last_angle: None,
+            position: 0,
+        }
+    }
+
+    /// Flag samples that need a velocity above `ticks_per_second` to be explained.
+    pub const fn with_max_velocity(mut self, ticks_per_second: u32) -> Self {
+        self.max_velocity = Some(ticks_per_second);
+        self
+    }
+
+    /// Change the maximum velocity, `None` disables ambiguity detection.
+    pub fn set_max_velocity(&mut self, ticks_per_second: Option<u32>) {
+        self.max_velocity = ticks_per_second;
+    }
+
+    /// Ticks per revolution.
+    pub fn resolution(&self) -> u16 {
+        self.resolution
+    }
+
+    /// Accumulated position in ticks.
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    /// Complete revolutions of the accumulated position.
+    pub fn turns(&self) -> i64 {
+        self.position.div_euclid(self.resolution as i64)
+    }
+
+    /// Overwrite the accumulated position, the next sample continues from it.
+    pub fn set_position(&mut self, position: i64) {
+        self.position = position;
+        self.last_angle = None;
+    }
+
+    /// Forget the last sample, the next one is taken as the angle closest to the current position.
+    pub fn reset(&mut self) {
+        self.last_angle = None;
+    }
+
+    /// Feed a new angle, sampled `elapsed` after the previous one, and get the accumulated position.
+    pub fn update(&mut self, angle: u16, elapsed: Duration) -> Result<i64, Error> {
+        if angle >= self.resolution {
+            return Err(Error::OutOfRange(angle));
+        }
+
+        let Some(last_angle) = self.last_angle else {
+            // Pick up from wherever the position was left
+            let current = self.position.rem_euclid(self.resolution as i64) as u16;
+            self.position += self.shortest_delta(current, angle) as i64;
+            self.last_angle = Some(angle);
+            return Ok(self.position);
+        };
+
+        let delta = self.shortest_delta(last_angle, angle);
+        self.position += delta as i64;
+        self.last_angle = Some(angle);
+
+        if let Some(max_velocity) = self.max_velocity {
+            let limit = (max_velocity as u128 * elapsed.as_micros() / 1_000_000)
+                .min(u32::MAX as u128) as u32;
+            if limit >= self.resolution as u32 / 2 || delta.unsigned_abs() > limit {
+                return Err(Error::Ambiguous { delta, limit });
+            }
+        }
+
+        Ok(self.position)
+    }
+
+    fn shortest_delta(&self, from: u16, to: u16) -> i32 {
+        let resolution = self.resolution as i32;
+        let delta = (to as i32 - from as i32).rem_euclid(resolution);
+        if delta > resolution / 2 {
+            delta - resolution
+        } else {
+            delta
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+
+    use crate::multi_turn::{Error, MultiTurnTracker};
+
+    const SAMPLE: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn starts_at_first_angle() {
+        let mut tracker = MultiTurnTracker::default();
+        assert_eq!(tracker.update(1000, SAMPLE), Ok(1000));
+        let mut tracker = MultiTurnTracker::default();
+        assert_eq!(tracker.update(4000, SAMPLE), Ok(-96));
+    }
+
+    #[test]
+    fn counts_turns_forward_and_backward() {
+        let mut tracker = MultiTurnTracker::default();
+        let mut angle = 0u16;
+        tracker.update(angle, SAMPLE).unwrap();
+        for _ in 0..(3 * 4096 / 100) {
+            angle = (angle + 100) % 4096;
+            tracker.update(angle, SAMPLE).unwrap();
+        }
+        assert_eq!(tracker.position(), 12200);
+        assert_eq!(tracker.turns(), 2);
+
+        for _ in 0..(5 * 4096 / 100) {
+            angle = (angle + 4096 - 100) % 4096;
+            tracker.update(angle, SAMPLE).unwrap();
+        }
+        assert_eq!(tracker.position(), 12200 - 20400);
+        assert_eq!(tracker.turns(), -3);
+    }
+
+    #[test]
+    fn flags_moves_faster_than_max_velocity() {
+        // 100 ticks per millisecond
+        let mut tracker = MultiTurnTracker::default().with_max_velocity(100_000);
+        tracker.update(0, SAMPLE).unwrap();
+        assert_eq!(tracker.update(100, SAMPLE), Ok(100));
+        assert_eq!(
+            tracker.update(300, SAMPLE),
+            Err(Error::Ambiguous {
+                delta: 200,
+                limit: 100
+            })
+        );
+        // Still follows the shortest path
+        assert_eq!(tracker.position(), 300);
+    }
+
+    #[test]
+    fn flags_samples_too_far_apart() {
+        let mut tracker = MultiTurnTracker::default().with_max_velocity(100_000);
+        tracker.update(0, SAMPLE).unwrap();
+        assert_eq!(
+            tracker.update(10, Duration::from_millis(30)),
+            Err(Error::Ambiguous {
+                delta: 10,
+                limit: 3000
+            })
+        );
+    }
+
+    #[test]
+    fn resumes_from_set_position() {
+        let mut tracker = MultiTurnTracker::default();
+        tracker.update(100, SAMPLE).unwrap();
+        tracker.set_position(-8192 + 100);
+        assert_eq!(tracker.update(90, SAMPLE), Ok(-8192 + 90));
+        tracker.reset();
+        assert_eq!(tracker.update(4090, SAMPLE), Ok(-8192 - 6));
+    }
+
+    #[test]
+    fn rejects_out_of_range_angles() {
+        let mut tracker = MultiTurnTracker::default();
+        assert_eq!(tracker.update(4096, SAMPLE), Err(Error::OutOfRange(4096)));
+    }
+}
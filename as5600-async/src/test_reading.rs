@@ -7,7 +7,8 @@ use crate::{
     status::{self, Status},
     As5600,
 };
-use embedded_hal_mock::i2c::{Mock, Transaction};
+use embassy_futures::block_on;
+use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
 
 #[test]
 fn detects_magnet() {
@@ -38,7 +39,7 @@ fn detects_magnet() {
     let mut as5600 = As5600::new(i2c);
     expected_status
         .iter()
-        .map(|s| (as5600.magnet_status(), s))
+        .map(|s| (block_on(as5600.magnet_status()), s))
         .all(|(a, b)| a == *b);
     as5600.release().done();
 }
@@ -56,7 +57,7 @@ fn reads_zmco_register() {
     let mut as5600 = As5600::new(i2c);
     expected_status
         .iter()
-        .map(|s| (as5600.zmco(), *s))
+        .map(|s| (block_on(as5600.zmco()), *s))
         .all(|(a, b)| a == Ok(b));
     as5600.release().done();
 }
@@ -69,7 +70,10 @@ fn reads_zero_position_register() {
         vec![0b1001_1010, 0b1010_1111],
     )]);
     let mut as5600 = As5600::new(i2c);
-    assert_eq!(0b0000_1010_1010_1111, as5600.zero_position().unwrap());
+    assert_eq!(
+        0b0000_1010_1010_1111,
+        block_on(as5600.zero_position()).unwrap()
+    );
     as5600.release().done();
 }
 
@@ -81,7 +85,10 @@ fn reads_maximum_position_register() {
         vec![0b1101_0010, 0b0010_1010],
     )]);
     let mut as5600 = As5600::new(i2c);
-    assert_eq!(0b0000_0010_0010_1010, as5600.maximum_position().unwrap());
+    assert_eq!(
+        0b0000_0010_0010_1010,
+        block_on(as5600.maximum_position()).unwrap()
+    );
     as5600.release().done();
 }
 
@@ -93,7 +100,10 @@ fn reads_maximum_angle_register() {
         vec![0b0001_1110, 0b1010_1011],
     )]);
     let mut as5600 = As5600::new(i2c);
-    assert_eq!(0b0000_1110_1010_1011, as5600.maximum_angle().unwrap());
+    assert_eq!(
+        0b0000_1110_1010_1011,
+        block_on(as5600.maximum_angle()).unwrap()
+    );
     as5600.release().done();
 }
 
@@ -114,7 +124,7 @@ fn reads_config_register() {
         watchdog_state: WatchdogState::On,
     };
     let mut as5600 = As5600::new(i2c);
-    assert_eq!(expected_config, as5600.config().unwrap());
+    assert_eq!(expected_config, block_on(as5600.config()).unwrap());
     as5600.release().done();
 }
 
@@ -127,7 +137,7 @@ fn reads_raw_angle_register() {
     )]);
     let expected_angle = 0x0123;
     let mut as5600 = As5600::new(i2c);
-    assert_eq!(expected_angle, as5600.raw_angle().unwrap());
+    assert_eq!(expected_angle, block_on(as5600.raw_angle()).unwrap());
     as5600.release().done();
 }
 
@@ -140,7 +150,7 @@ fn reads_angle_register() {
     )]);
     let expected_angle = 0x0842;
     let mut as5600 = As5600::new(i2c);
-    assert_eq!(expected_angle, as5600.angle().unwrap());
+    assert_eq!(expected_angle, block_on(as5600.angle()).unwrap());
     as5600.release().done();
 }
 
@@ -149,7 +159,10 @@ fn reads_automatic_gain_control_register() {
     let i2c = Mock::new(&[Transaction::write_read(0x36, vec![0x1a], vec![0b0101_1010])]);
     let expected_agc = 0b0101_1010;
     let mut as5600 = As5600::new(i2c);
-    assert_eq!(expected_agc, as5600.automatic_gain_control().unwrap());
+    assert_eq!(
+        expected_agc,
+        block_on(as5600.automatic_gain_control()).unwrap()
+    );
     as5600.release().done();
 }
 
@@ -163,6 +176,6 @@ fn reads_magnitude_register() {
 
     let expected_magnitude = 0b0000_1010_1101_0101;
     let mut as5600 = As5600::new(i2c);
-    assert_eq!(expected_magnitude, as5600.magnitude().unwrap());
+    assert_eq!(expected_magnitude, block_on(as5600.magnitude()).unwrap());
     as5600.release().done();
 }
@@ -6,7 +6,8 @@ use crate::{
     error::Error,
     As5600,
 };
-use embedded_hal_mock::i2c::{Mock, Transaction};
+use embassy_futures::block_on;
+use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
 
 #[test]
 fn set_zero_position() {
@@ -18,7 +19,7 @@ fn set_zero_position() {
     ]);
     let mut as5600 = As5600::new(i2c);
     for angle in [0x1AAF, 0x0110, 0x0ACA, 0x010B] {
-        as5600.set_zero_position(angle).unwrap();
+        block_on(as5600.set_zero_position(angle)).unwrap();
     }
     as5600.release().done();
 }
@@ -33,7 +34,7 @@ fn set_maximum_position() {
     ]);
     let mut as5600 = As5600::new(i2c);
     for angle in [0xAFAF, 0x2010, 0x1FAF, 0x1100] {
-        as5600.set_maximum_position(angle).unwrap();
+        block_on(as5600.set_maximum_position(angle)).unwrap();
     }
     as5600.release().done();
 }
@@ -48,7 +49,7 @@ fn set_maximum_angle() {
     ]);
     let mut as5600 = As5600::new(i2c);
     for angle in [0x0FFA, 0x0001, 0xAFFA, 0x1000] {
-        as5600.set_maximum_angle(angle).unwrap();
+        block_on(as5600.set_maximum_angle(angle)).unwrap();
     }
     as5600.release().done();
 }
@@ -72,7 +73,7 @@ fn set_config() {
         Transaction::write(0x36, vec![0x07, top_most_set, config_bytes[1]]),
     ]);
     let mut as5600 = As5600::new(i2c);
-    as5600.set_config(config).unwrap();
+    block_on(as5600.set_config(config)).unwrap();
     as5600.release().done();
 }
 
@@ -83,19 +84,19 @@ fn burn_angle_succeeds() {
         Transaction::write_read(0x36, vec![0x0b], vec![0x20]),
         Transaction::write(0x36, vec![0xFF, 0x80]),
     ]);
-    let mut delay = embedded_hal_mock::delay::MockNoop;
+    let mut delay = embedded_hal_mock::eh1::delay::NoopDelay;
     let mut as5600 = As5600::new(i2c);
-    as5600.persist_position_settings(&mut delay).unwrap();
+    block_on(as5600.persist_position_settings(&mut delay)).unwrap();
     as5600.release().done();
 }
 
 #[test]
 fn burn_angle_fails_due_to_zmco() {
     let i2c = Mock::new(&[Transaction::write_read(0x36, vec![0x00], vec![0b0000_0011])]);
-    let mut delay = embedded_hal_mock::delay::MockNoop;
+    let mut delay = embedded_hal_mock::eh1::delay::NoopDelay;
     let mut as5600 = As5600::new(i2c);
     assert_eq!(
-        as5600.persist_position_settings(&mut delay).unwrap_err(),
+        block_on(as5600.persist_position_settings(&mut delay)).unwrap_err(),
         Error::MaximumPositionPersistsReached
     );
     as5600.release().done();
@@ -107,10 +108,10 @@ fn burn_angle_fails_due_to_magnet_detection() {
         Transaction::write_read(0x36, vec![0x00], vec![0b0000_0001]),
         Transaction::write_read(0x36, vec![0x0b], vec![0x10]),
     ]);
-    let mut delay = embedded_hal_mock::delay::MockNoop;
+    let mut delay = embedded_hal_mock::eh1::delay::NoopDelay;
     let mut as5600 = As5600::new(i2c);
     assert_eq!(
-        as5600.persist_position_settings(&mut delay).unwrap_err(),
+        block_on(as5600.persist_position_settings(&mut delay)).unwrap_err(),
         Error::MagnetRequired
     );
     as5600.release().done();
@@ -122,23 +123,19 @@ fn burn_settings_succeeds() {
         Transaction::write_read(0x36, vec![0x00], vec![0b0000_0000]),
         Transaction::write(0x36, vec![0xFF, 0x40]),
     ]);
-    let mut delay = embedded_hal_mock::delay::MockNoop;
+    let mut delay = embedded_hal_mock::eh1::delay::NoopDelay;
     let mut as5600 = As5600::new(i2c);
-    as5600
-        .persist_maximum_angle_and_config_settings(&mut delay)
-        .unwrap();
+    block_on(as5600.persist_maximum_angle_and_config_settings(&mut delay)).unwrap();
     as5600.release().done();
 }
 
 #[test]
 fn burn_settings_fails_when_zmco_is_not_zero() {
     let i2c = Mock::new(&[Transaction::write_read(0x36, vec![0x00], vec![0b0000_0001])]);
-    let mut delay = embedded_hal_mock::delay::MockNoop;
+    let mut delay = embedded_hal_mock::eh1::delay::NoopDelay;
     let mut as5600 = As5600::new(i2c);
     assert_eq!(
-        as5600
-            .persist_maximum_angle_and_config_settings(&mut delay)
-            .unwrap_err(),
+        block_on(as5600.persist_maximum_angle_and_config_settings(&mut delay)).unwrap_err(),
         Error::MangConfigPersistenceExhausted
     );
     as5600.release().done();
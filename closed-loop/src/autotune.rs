@@ -0,0 +1,195 @@
+use crate::correction::CorrectionConfig;
+
+// Gain never drops below this, the corrector still has to get somewhere
+const MIN_GAIN: f32 = 0.1;
+
+// Largest correction ever suggested, in steps
+const MAX_CORRECTION_STEPS: u32 = 256;
+
+/// Autotune errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// A phase of the test move has no samples.
+    NoSamples,
+
+    /// The motor did not settle back within the noise after the move, this following error was
+    /// left. Usually steps were lost because the test move was too fast.
+    NotSettled(i32),
+}
+
+/// Settings computed from a test move.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tuning {
+    /// Following error correction settings.
+    pub correction: CorrectionConfig,
+    /// Following error, in steps, above which the motor is stalling.
+    pub stall_threshold: u32,
+}
+
+/// Following error recorded at rest, during and after a test move.
+/// Errors are measured minus commanded position, in steps.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StepResponse {
+    direction: i32,
+    noise: Option<(i32, i32)>,
+    peak_lag: Option<u32>,
+    overshoot: u32,
+    last_outside: u32,
+    last_error: Option<i32>,
+}
+
+impl StepResponse {
+    /// Start recording the response to a move of `steps`, the sign gives its direction.
+    pub const fn new(steps: i32) -> Self {
+        Self {
+            direction: if steps < 0 { -1 } else { 1 },
+            noise: None,
+            peak_lag: None,
+            overshoot: 0,
+            last_outside: 0,
+            last_error: None,
+        }
+    }
+
+    /// Record a sample at rest before the move.
+    pub fn rest(&mut self, following_error: i32) {
+        self.noise = Some(match self.noise {
+            Some((min, max)) => (min.min(following_error), max.max(following_error)),
+            None => (following_error, following_error),
+        });
+    }
+
+    /// Record a sample while the move is going out.
+    pub fn moving(&mut self, following_error: i32) {
+        // Falling behind the move is a negative error along its direction
+        let lag = (-following_error * self.direction).max(0) as u32;
+        self.peak_lag = Some(self.peak_lag.map_or(lag, |peak| peak.max(lag)));
+    }
+
+    /// Record a sample taken `elapsed` ticks after the last step of the move.
+    pub fn settling(&mut self, following_error: i32, elapsed: u32) {
+        let ahead = (following_error * self.direction).max(0) as u32;
+        self.overshoot = self.overshoot.max(ahead);
+        if following_error.unsigned_abs() > self.band() {
+            self.last_outside = elapsed;
+        }
+        self.last_error = Some(following_error);
+    }
+
+    /// Following error, in steps, that is noise rather than motion.
+    pub fn band(&self) -> u32 {
+        self.noise
+            .map_or(0, |(min, max)| min.unsigned_abs().max(max.unsigned_abs()))
+            + 1
+    }
+
+    /// Largest lag behind the move, in steps.
+    pub fn peak_lag(&self) -> Option<u32> {
+        self.peak_lag
+    }
+
+    /// Largest overshoot past the end of the move, in steps.
+    pub fn overshoot(&self) -> u32 {
+        self.overshoot
+    }
+
+    /// Ticks after the end of the move until the following error stayed within the band.
+    pub fn settle_ticks(&self) -> u32 {
+        self.last_outside
+    }
+
+    /// Compute the settings for corrective steps `step_interval` ticks apart.
+    /// The gain is lowered as much as the motor overshoots, so a correction doesn't overshoot
+    /// by more than the band. Corrections are limited to what goes out before the motor settled,
+    /// and a stall is twice the lag the motor needed to follow the move.
+    pub fn tune(&self, step_interval: u32) -> Result<Tuning, Error> {
+        let (Some(_), Some(peak_lag), Some(last_error)) =
+            (self.noise, self.peak_lag, self.last_error)
+        else {
+            return Err(Error::NoSamples);
+        };
+        let band = self.band();
+        if last_error.unsigned_abs() > band {
+            return Err(Error::NotSettled(last_error));
+        }
+
+        let gain = (1. / (1. + self.overshoot as f32 / peak_lag.max(1) as f32)).max(MIN_GAIN);
+        let max_steps =
+            (self.last_outside / step_interval.max(1)).clamp(band + 1, MAX_CORRECTION_STEPS);
+
+        Ok(Tuning {
+            correction: CorrectionConfig {
+                band,
+                gain,
+                max_steps,
+            },
+            stall_threshold: 2 * peak_lag.max(band),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, StepResponse};
+
+    fn response(steps: i32) -> StepResponse {
+        let direction = steps.signum();
+        let mut response = StepResponse::new(steps);
+        for error in [0, 1, -1, 0] {
+            response.rest(error);
+        }
+        for lag in [2, 6, 10, 8, 4] {
+            response.moving(-lag * direction);
+        }
+        for (error, elapsed) in [(3, 100), (5, 200), (-1, 300), (0, 400)] {
+            response.settling(error * direction, elapsed);
+        }
+        response
+    }
+
+    #[test]
+    fn needs_every_phase() {
+        let mut response = StepResponse::new(100);
+        assert_eq!(response.tune(100), Err(Error::NoSamples));
+        response.rest(0);
+        response.moving(-3);
+        assert_eq!(response.tune(100), Err(Error::NoSamples));
+        response.settling(0, 100);
+        assert!(response.tune(100).is_ok());
+    }
+
+    #[test]
+    fn measures_response() {
+        for steps in [100, -100] {
+            let response = response(steps);
+            assert_eq!(response.band(), 2);
+            assert_eq!(response.peak_lag(), Some(10));
+            assert_eq!(response.overshoot(), 5);
+            assert_eq!(response.settle_ticks(), 200);
+        }
+    }
+
+    #[test]
+    fn tunes_from_response() {
+        let tuning = response(100).tune(20).unwrap();
+        assert_eq!(tuning.correction.band, 2);
+        // Overshoots by half of the lag
+        assert!((tuning.correction.gain - 2. / 3.).abs() < 1e-6);
+        assert_eq!(tuning.correction.max_steps, 10);
+        assert_eq!(tuning.stall_threshold, 20);
+    }
+
+    #[test]
+    fn limits_correction_steps() {
+        let response = response(100);
+        assert_eq!(response.tune(1000).unwrap().correction.max_steps, 3);
+        assert_eq!(response.tune(0).unwrap().correction.max_steps, 200);
+    }
+
+    #[test]
+    fn lost_steps_fail() {
+        let mut response = response(100);
+        response.settling(-64, 500);
+        assert_eq!(response.tune(20), Err(Error::NotSettled(-64)));
+    }
+}
@@ -0,0 +1,97 @@
+/// Lost motion after direction reversals, split by the direction reversed into.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Backlash {
+    forward_sum: i64,
+    forward_count: u32,
+    backward_sum: i64,
+    backward_count: u32,
+}
+
+impl Backlash {
+    /// No reversal recorded yet.
+    pub const fn new() -> Self {
+        Self {
+            forward_sum: 0,
+            forward_count: 0,
+            backward_sum: 0,
+            backward_count: 0,
+        }
+    }
+
+    /// Record the move following a direction reversal, `commanded` and `measured` share a unit and
+    /// are signed in the direction of the move. A positive `commanded` is a reversal into forward.
+    pub fn record(&mut self, commanded: i32, measured: i32) {
+        let lost = (commanded as i64 - measured as i64) * commanded.signum() as i64;
+        if commanded >= 0 {
+            self.forward_sum += lost;
+            self.forward_count += 1;
+        } else {
+            self.backward_sum += lost;
+            self.backward_count += 1;
+        }
+    }
+
+    /// Mean motion lost reversing into forward.
+    pub fn forward(&self) -> Option<f32> {
+        mean(self.forward_sum, self.forward_count)
+    }
+
+    /// Mean motion lost reversing into backward.
+    pub fn backward(&self) -> Option<f32> {
+        mean(self.backward_sum, self.backward_count)
+    }
+
+    /// Mean motion lost over every reversal.
+    pub fn backlash(&self) -> Option<f32> {
+        mean(
+            self.forward_sum + self.backward_sum,
+            self.forward_count + self.backward_count,
+        )
+    }
+
+    /// Difference between the motion lost reversing into forward and into backward.
+    pub fn hysteresis(&self) -> Option<f32> {
+        Some(self.forward()? - self.backward()?)
+    }
+}
+
+fn mean(sum: i64, count: u32) -> Option<f32> {
+    if count == 0 {
+        None
+    } else {
+        Some(sum as f32 / count as f32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Backlash;
+
+    #[test]
+    fn nothing_recorded() {
+        let backlash = Backlash::new();
+        assert_eq!(backlash.backlash(), None);
+        assert_eq!(backlash.hysteresis(), None);
+    }
+
+    #[test]
+    fn measures_lost_motion() {
+        let mut backlash = Backlash::default();
+        // 100 steps commanded, the encoder only moved 96 and 94 of them
+        backlash.record(-100, -96);
+        backlash.record(100, 94);
+        backlash.record(-100, -96);
+        backlash.record(100, 94);
+        assert_eq!(backlash.backward(), Some(4.));
+        assert_eq!(backlash.forward(), Some(6.));
+        assert_eq!(backlash.backlash(), Some(5.));
+        assert_eq!(backlash.hysteresis(), Some(2.));
+    }
+
+    #[test]
+    fn overshoot_is_negative() {
+        let mut backlash = Backlash::new();
+        backlash.record(50, 52);
+        assert_eq!(backlash.forward(), Some(-2.));
+    }
+}
@@ -0,0 +1,285 @@
+/// Maximum number of points recorded over one revolution.
+pub const MAX_CALIBRATION_POINTS: usize = 256;
+
+/// Minimum number of points recorded over one revolution. The travel between two points is taken
+/// the shortest way around, so it has to stay under half a revolution even with the distortion of
+/// an off-axis magnet added on top. Quarter revolutions leave as much again for the distortion.
+pub const MIN_CALIBRATION_POINTS: usize = 4;
+
+/// Calibration table errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// Less than [`MIN_CALIBRATION_POINTS`] or more than [`MAX_CALIBRATION_POINTS`] points were
+    /// given.
+    PointCount(usize),
+
+    /// Encoder resolution of 0.
+    Resolution,
+
+    /// The recorded angles do not cover one revolution, holds the travelled distance in ticks.
+    IncompleteRevolution(i32),
+
+    /// The recorded angles do not strictly advance at the given point.
+    NotMonotonic(usize),
+}
+
+/// Encoder linearization table, recorded by stepping a full revolution in equal increments.
+///
+/// Raw encoder angles are mapped onto the ideal angle of the commanded position, which removes the
+/// nonlinearity caused by an off-axis magnet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalibrationTable {
+    resolution: u16,
+    origin: u16,
+    inverted: bool,
+    points: u16,
+    // Distance travelled from `origin` in the direction of travel, in ticks, for each point
+    offsets: [u16; MAX_CALIBRATION_POINTS],
+}
+
+impl CalibrationTable {
+    /// Build a table from the encoder angles recorded at equally spaced positions over one
+    /// forward revolution, starting at `samples[0]`.
+    pub fn from_samples(resolution: u16, samples: &[u16]) -> Result<Self, Error> {
+        if resolution == 0 {
+            return Err(Error::Resolution);
+        }
+        if samples.len() < MIN_CALIBRATION_POINTS || samples.len() > MAX_CALIBRATION_POINTS {
+            return Err(Error::PointCount(samples.len()));
+        }
+
+        let mut travelled = [0i32; MAX_CALIBRATION_POINTS];
+        let mut total = 0i32;
+        for (index, pair) in samples.windows(2).enumerate() {
+            total += wrapped_delta(resolution, pair[0], pair[1]);
+            travelled[index + 1] = total;
+        }
+        // Close the loop back to the first point
+        total += wrapped_delta(resolution, samples[samples.len() - 1], samples[0]);
+
+        let expected = resolution as i32;
+        if total.abs() < expected / 2 || total.abs() > expected + expected / 2 {
+            return Err(Error::IncompleteRevolution(total));
+        }
+
+        let inverted = total.is_negative();
+        let mut offsets = [0u16; MAX_CALIBRATION_POINTS];
+        for (index, travelled) in travelled[..samples.len()].iter().enumerate() {
+            offsets[index] = travelled.unsigned_abs() as u16;
+        }
+
+        let table = Self::from_parts(resolution, samples[0], inverted, &offsets[..samples.len()])?;
+        Ok(table)
+    }
+
+    /// Rebuild a table from a previously recorded one, see [`CalibrationTable::offsets`].
+    pub fn from_parts(
+        resolution: u16,
+        origin: u16,
+        inverted: bool,
+        offsets: &[u16],
+    ) -> Result<Self, Error> {
+        if resolution == 0 {
+            return Err(Error::Resolution);
+        }
+        if offsets.len() < MIN_CALIBRATION_POINTS || offsets.len() > MAX_CALIBRATION_POINTS {
+            return Err(Error::PointCount(offsets.len()));
+        }
+        if offsets[0] != 0 {
+            return Err(Error::NotMonotonic(0));
+        }
+        for (index, pair) in offsets.windows(2).enumerate() {
+            if pair[1] <= pair[0] {
+                return Err(Error::NotMonotonic(index + 1));
+            }
+        }
+        if offsets[offsets.len() - 1] >= resolution {
+            return Err(Error::NotMonotonic(offsets.len() - 1));
+        }
+
+        let mut table = Self {
+            resolution,
+            origin: origin % resolution,
+            inverted,
+            points: offsets.len() as u16,
+            offsets: [0; MAX_CALIBRATION_POINTS],
+        };
+        table.offsets[..offsets.len()].copy_from_slice(offsets);
+        Ok(table)
+    }
+
+    /// Encoder resolution in ticks per revolution.
+    pub fn resolution(&self) -> u16 {
+        self.resolution
+    }
+
+    /// Raw angle of the first point.
+    pub fn origin(&self) -> u16 {
+        self.origin
+    }
+
+    /// Whether the raw angle decreased while stepping forward.
+    pub fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Number of points in the table.
+    pub fn points(&self) -> u16 {
+        self.points
+    }
+
+    /// Recorded distance from the origin, in ticks, of every point.
+    pub fn offsets(&self) -> &[u16] {
+        &self.offsets[..self.points as usize]
+    }
+
+    /// Largest difference between a recorded point and its ideal angle, in ticks.
+    pub fn max_error(&self) -> u16 {
+        self.offsets()
+            .iter()
+            .enumerate()
+            .map(|(index, offset)| {
+                (*offset as i32 - self.ideal_offset(index as f32) as i32).unsigned_abs()
+            })
+            .max()
+            .unwrap_or(0) as u16
+    }
+
+    /// Map a raw encoder angle onto the linearized angle, in the same ticks and orientation.
+    pub fn correct(&self, raw_angle: u16) -> u16 {
+        let resolution = self.resolution as i32;
+        let mut distance = (raw_angle % self.resolution) as i32 - self.origin as i32;
+        if self.inverted {
+            distance = -distance;
+        }
+        let distance = distance.rem_euclid(resolution) as u16;
+
+        let offsets = self.offsets();
+        // Index of the last point at or before `distance`, the first offset is always 0
+        let index = offsets.partition_point(|offset| *offset <= distance) - 1;
+        let start = offsets[index];
+        let end = offsets.get(index + 1).copied().unwrap_or(self.resolution);
+        let fraction = (distance - start) as f32 / (end - start) as f32;
+
+        let ideal = (self.ideal_offset(index as f32 + fraction) + 0.5) as i32;
+        let corrected = if self.inverted {
+            self.origin as i32 - ideal
+        } else {
+            self.origin as i32 + ideal
+        };
+        corrected.rem_euclid(resolution) as u16
+    }
+
+    fn ideal_offset(&self, point: f32) -> f32 {
+        point * self.resolution as f32 / self.points as f32
+    }
+}
+
+// Shortest signed distance from `from` to `to` on a circle of `resolution` ticks
+fn wrapped_delta(resolution: u16, from: u16, to: u16) -> i32 {
+    let resolution = resolution as i32;
+    let delta = (to as i32 - from as i32).rem_euclid(resolution);
+    if delta > resolution / 2 {
+        delta - resolution
+    } else {
+        delta
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CalibrationTable, Error};
+
+    // Angles of an off-axis magnet, reading up to `amplitude` ticks ahead or behind of the truth
+    fn distorted(
+        resolution: u16,
+        points: u16,
+        origin: u16,
+        amplitude: f32,
+        inverted: bool,
+    ) -> [u16; 64] {
+        let mut samples = [0u16; 64];
+        for (index, sample) in samples[..points as usize].iter_mut().enumerate() {
+            let ideal = index as f32 * resolution as f32 / points as f32;
+            let error = match index % 4 {
+                1 => amplitude,
+                3 => -amplitude,
+                _ => 0.,
+            };
+            let travelled = (ideal + error) as i32;
+            let angle = if inverted {
+                origin as i32 - travelled
+            } else {
+                origin as i32 + travelled
+            };
+            *sample = angle.rem_euclid(resolution as i32) as u16;
+        }
+        samples
+    }
+
+    #[test]
+    fn maps_recorded_points_onto_ideal_angles() {
+        let samples = distorted(4096, 64, 4000, 20., false);
+        let table = CalibrationTable::from_samples(4096, &samples).unwrap();
+        assert_eq!(table.max_error(), 20);
+        for (index, sample) in samples.iter().enumerate() {
+            let ideal = ((4000 + index * 64) % 4096) as u16;
+            assert_eq!(table.correct(*sample), ideal);
+        }
+    }
+
+    #[test]
+    fn handles_inverted_encoder() {
+        let samples = distorted(4096, 64, 100, 10., true);
+        let table = CalibrationTable::from_samples(4096, &samples).unwrap();
+        assert!(table.inverted());
+        for (index, sample) in samples.iter().enumerate() {
+            let ideal = (100 - index as i32 * 64).rem_euclid(4096) as u16;
+            assert_eq!(table.correct(*sample), ideal);
+        }
+    }
+
+    #[test]
+    fn interpolates_between_points() {
+        let samples = distorted(4096, 64, 0, 0., false);
+        let table = CalibrationTable::from_samples(4096, &samples).unwrap();
+        for angle in 0..4096 {
+            assert_eq!(table.correct(angle), angle);
+        }
+    }
+
+    #[test]
+    fn roundtrips_through_parts() {
+        let samples = distorted(4096, 64, 1234, 15., false);
+        let table = CalibrationTable::from_samples(4096, &samples).unwrap();
+        let rebuilt = CalibrationTable::from_parts(
+            table.resolution(),
+            table.origin(),
+            table.inverted(),
+            table.offsets(),
+        )
+        .unwrap();
+        assert_eq!(table, rebuilt);
+    }
+
+    #[test]
+    fn rejects_bad_recordings() {
+        assert_eq!(
+            CalibrationTable::from_samples(4096, &[0]),
+            Err(Error::PointCount(1))
+        );
+        // Half revolutions can't tell which way around the encoder went
+        assert_eq!(
+            CalibrationTable::from_samples(4096, &[0, 2048]),
+            Err(Error::PointCount(2))
+        );
+        assert_eq!(
+            CalibrationTable::from_samples(4096, &[0, 10, 20, 30]),
+            Err(Error::IncompleteRevolution(0))
+        );
+        assert_eq!(
+            CalibrationTable::from_samples(4096, &[0, 1024, 1000, 3000]),
+            Err(Error::NotMonotonic(2))
+        );
+    }
+}
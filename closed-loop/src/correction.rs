@@ -0,0 +1,111 @@
+/// Following error correction settings.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CorrectionConfig {
+    /// Following error, in steps, that is tolerated without correcting.
+    pub band: u32,
+    /// Fraction of the following error corrected per update.
+    pub gain: f32,
+    /// Maximum number of corrective steps issued per update, 0 disables correction.
+    pub max_steps: u32,
+}
+
+impl Default for CorrectionConfig {
+    fn default() -> Self {
+        Self {
+            band: 2,
+            gain: 1.0,
+            max_steps: 16,
+        }
+    }
+}
+
+/// Proportional corrector turning the encoder following error into corrective steps.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Corrector {
+    config: CorrectionConfig,
+}
+
+impl Corrector {
+    /// Create a new corrector with the given settings.
+    pub const fn new(config: CorrectionConfig) -> Self {
+        Self { config }
+    }
+
+    /// Get the current settings.
+    pub fn config(&self) -> CorrectionConfig {
+        self.config
+    }
+
+    /// Replace the current settings.
+    pub fn set_config(&mut self, config: CorrectionConfig) {
+        self.config = config
+    }
+
+    /// Get the corrective steps for a following error (measured minus commanded position, in steps).
+    /// A positive result means the motor has to step forward.
+    pub fn update(&mut self, following_error: i32) -> i32 {
+        let magnitude = following_error.unsigned_abs();
+        if magnitude <= self.config.band || self.config.max_steps == 0 {
+            return 0;
+        }
+
+        // Always issue at least one step once we are outside of the band, otherwise a small gain
+        // would leave us sitting on the band edge forever.
+        let steps = ((magnitude as f32 * self.config.gain) as u32).clamp(1, self.config.max_steps);
+
+        if following_error.is_positive() {
+            -(steps as i32)
+        } else {
+            steps as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CorrectionConfig, Corrector};
+
+    #[test]
+    fn ignores_error_inside_band() {
+        let mut corrector = Corrector::new(CorrectionConfig::default());
+        for error in -2..=2 {
+            assert_eq!(corrector.update(error), 0);
+        }
+    }
+
+    #[test]
+    fn corrects_against_error() {
+        let mut corrector = Corrector::new(CorrectionConfig::default());
+        assert_eq!(corrector.update(5), -5);
+        assert_eq!(corrector.update(-5), 5);
+    }
+
+    #[test]
+    fn clamps_to_max_steps() {
+        let mut corrector = Corrector::new(CorrectionConfig {
+            max_steps: 4,
+            ..CorrectionConfig::default()
+        });
+        assert_eq!(corrector.update(100), -4);
+        assert_eq!(corrector.update(-100), 4);
+    }
+
+    #[test]
+    fn small_gain_still_steps() {
+        let mut corrector = Corrector::new(CorrectionConfig {
+            gain: 0.1,
+            ..CorrectionConfig::default()
+        });
+        assert_eq!(corrector.update(3), -1);
+        assert_eq!(corrector.update(50), -5);
+    }
+
+    #[test]
+    fn zero_max_steps_disables_correction() {
+        let mut corrector = Corrector::new(CorrectionConfig {
+            max_steps: 0,
+            ..CorrectionConfig::default()
+        });
+        assert_eq!(corrector.update(1000), 0);
+    }
+}
@@ -0,0 +1,364 @@
+/// TMC2209 `IHOLD_IRUN` register address.
+pub const IHOLD_IRUN: u8 = 0x10;
+/// TMC2209 `IFCNT` register address, counts the writes the driver accepted.
+pub const IFCNT: u8 = 0x02;
+
+// Largest current scale setting of the TMC2209
+const MAX_CURRENT: u8 = 31;
+const SYNC: u8 = 0x05;
+const WRITE: u8 = 0x80;
+// Address the driver replies to reads with
+const MASTER: u8 = 0xff;
+
+/// TMC2209 `IHOLD_IRUN` register.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IholdIrun {
+    /// Standstill current scale, 0 to 31.
+    pub ihold: u8,
+    /// Run current scale, 0 to 31.
+    pub irun: u8,
+    /// Power down delay after standstill.
+    pub iholddelay: u8,
+}
+
+impl IholdIrun {
+    /// Unpack the register value.
+    pub fn from_bits(bits: u32) -> Self {
+        Self {
+            ihold: (bits & 0x1f) as u8,
+            irun: ((bits >> 8) & 0x1f) as u8,
+            iholddelay: ((bits >> 16) & 0x0f) as u8,
+        }
+    }
+
+    /// Pack into the register value.
+    pub fn to_bits(self) -> u32 {
+        (self.ihold.min(MAX_CURRENT) as u32)
+            | (self.irun.min(MAX_CURRENT) as u32) << 8
+            | (self.iholddelay.min(0x0f) as u32) << 16
+    }
+
+    /// Scale both currents, saturating at the largest setting.
+    pub fn scaled(self, scale: f32) -> Self {
+        let scale_current =
+            |current: u8| ((current as f32 * scale + 0.5) as u32).min(MAX_CURRENT as u32) as u8;
+        Self {
+            ihold: scale_current(self.ihold),
+            irun: scale_current(self.irun),
+            iholddelay: self.iholddelay,
+        }
+    }
+}
+
+/// TMC UART datagram CRC.
+pub fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in bytes {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            if (crc >> 7) ^ (byte & 1) != 0 {
+                crc = (crc << 1) ^ 0x07;
+            } else {
+                crc <<= 1;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+/// Build a register write datagram for the driver at `slave` address.
+pub fn write_datagram(slave: u8, register: u8, value: u32) -> [u8; 8] {
+    let mut datagram = [0u8; 8];
+    datagram[0] = SYNC;
+    datagram[1] = slave;
+    datagram[2] = register | WRITE;
+    datagram[3..7].copy_from_slice(&value.to_be_bytes());
+    datagram[7] = crc8(&datagram[..7]);
+    datagram
+}
+
+/// Parse a register write datagram, returns the slave address, register and value.
+pub fn parse_write_datagram(datagram: &[u8]) -> Option<(u8, u8, u32)> {
+    let datagram: &[u8; 8] = datagram.try_into().ok()?;
+    if datagram[0] & 0x0f != SYNC || datagram[2] & WRITE == 0 || crc8(&datagram[..7]) != datagram[7]
+    {
+        return None;
+    }
+    let value = u32::from_be_bytes(datagram[3..7].try_into().unwrap());
+    Some((datagram[1], datagram[2] & !WRITE, value))
+}
+
+/// Parse a register read request datagram, returns the slave address and register.
+pub fn parse_read_request(datagram: &[u8]) -> Option<(u8, u8)> {
+    let datagram: &[u8; 4] = datagram.try_into().ok()?;
+    if datagram[0] & 0x0f != SYNC || datagram[2] & WRITE != 0 || crc8(&datagram[..3]) != datagram[3]
+    {
+        return None;
+    }
+    Some((datagram[1], datagram[2]))
+}
+
+/// Take `writes` off the counter in an `IFCNT` read reply, so whoever reads it only sees their own
+/// writes. Returns false and leaves the datagram alone if it is not a valid `IFCNT` reply.
+pub fn hide_writes(reply: &mut [u8], writes: u8) -> bool {
+    let Ok(reply) = <&mut [u8; 8]>::try_from(reply) else {
+        return false;
+    };
+    if reply[0] & 0x0f != SYNC
+        || reply[1] != MASTER
+        || reply[2] != IFCNT
+        || crc8(&reply[..7]) != reply[7]
+    {
+        return false;
+    }
+    reply[6] = reply[6].wrapping_sub(writes);
+    reply[7] = crc8(&reply[..7]);
+    true
+}
+
+/// Adaptive current settings, scales are relative to the current configured by the host.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CurrentConfig {
+    /// Current scale while the following error is at or below `error_low`.
+    pub reduced: f32,
+    /// Current scale once the following error reaches `error_high`.
+    pub boosted: f32,
+    /// Following error, in steps, below which the current is reduced.
+    pub error_low: u32,
+    /// Following error, in steps, at which the current is fully boosted.
+    pub error_high: u32,
+    /// Consecutive samples asking for less current before it is lowered.
+    pub settle_samples: u8,
+}
+
+impl Default for CurrentConfig {
+    fn default() -> Self {
+        Self {
+            reduced: 0.6,
+            boosted: 1.2,
+            error_low: 4,
+            error_high: 32,
+            settle_samples: 50,
+        }
+    }
+}
+
+/// Picks the driver current from the following error. The current is raised as soon as the error
+/// grows, but only lowered once the error stayed small for a while.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CurrentPolicy {
+    config: CurrentConfig,
+    baseline: IholdIrun,
+    applied: Option<IholdIrun>,
+    lower_samples: u8,
+}
+
+impl CurrentPolicy {
+    /// Create a new policy scaling the `baseline` current.
+    pub const fn new(config: CurrentConfig, baseline: IholdIrun) -> Self {
+        Self {
+            config,
+            baseline,
+            applied: None,
+            lower_samples: 0,
+        }
+    }
+
+    /// Get the current settings.
+    pub fn config(&self) -> CurrentConfig {
+        self.config
+    }
+
+    /// Replace the current settings.
+    pub fn set_config(&mut self, config: CurrentConfig) {
+        self.config = config;
+    }
+
+    /// Current the scales are relative to.
+    pub fn baseline(&self) -> IholdIrun {
+        self.baseline
+    }
+
+    /// Replace the current the scales are relative to, the next update writes the register again.
+    pub fn set_baseline(&mut self, baseline: IholdIrun) {
+        if self.baseline != baseline {
+            self.baseline = baseline;
+            self.applied = None;
+        }
+    }
+
+    /// Register value last returned by [`CurrentPolicy::update`].
+    pub fn applied(&self) -> Option<IholdIrun> {
+        self.applied
+    }
+
+    /// Forget what was written, the next update writes the register again.
+    pub fn reset(&mut self) {
+        self.applied = None;
+        self.lower_samples = 0;
+    }
+
+    /// Current scale for a following error, in steps.
+    pub fn scale(&self, following_error: i32) -> f32 {
+        let error = following_error.unsigned_abs();
+        let CurrentConfig {
+            reduced,
+            boosted,
+            error_low,
+            error_high,
+            ..
+        } = self.config;
+
+        if error <= error_low {
+            reduced
+        } else if error >= error_high || error_high <= error_low {
+            boosted
+        } else {
+            let fraction = (error - error_low) as f32 / (error_high - error_low) as f32;
+            reduced + (boosted - reduced) * fraction
+        }
+    }
+
+    /// Feed a following error sample, in steps.
+    /// Returns the register value to write when the current has to change.
+    pub fn update(&mut self, following_error: i32) -> Option<IholdIrun> {
+        let target = self.baseline.scaled(self.scale(following_error));
+
+        let Some(applied) = self.applied else {
+            self.lower_samples = 0;
+            self.applied = Some(target);
+            return self.applied;
+        };
+
+        if target.irun > applied.irun {
+            self.lower_samples = 0;
+            self.applied = Some(target);
+            return self.applied;
+        }
+
+        if target.irun < applied.irun {
+            self.lower_samples = self.lower_samples.saturating_add(1);
+            if self.lower_samples >= self.config.settle_samples {
+                self.lower_samples = 0;
+                self.applied = Some(target);
+                return self.applied;
+            }
+        } else {
+            self.lower_samples = 0;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        crc8, hide_writes, parse_read_request, parse_write_datagram, write_datagram, CurrentConfig,
+        CurrentPolicy, IholdIrun, IFCNT, IHOLD_IRUN,
+    };
+
+    const BASELINE: IholdIrun = IholdIrun {
+        ihold: 10,
+        irun: 20,
+        iholddelay: 8,
+    };
+
+    fn policy() -> CurrentPolicy {
+        CurrentPolicy::new(
+            CurrentConfig {
+                reduced: 0.5,
+                boosted: 1.5,
+                error_low: 4,
+                error_high: 24,
+                settle_samples: 3,
+            },
+            BASELINE,
+        )
+    }
+
+    #[test]
+    fn register_roundtrip() {
+        assert_eq!(IholdIrun::from_bits(BASELINE.to_bits()), BASELINE);
+        assert_eq!(BASELINE.to_bits(), 0x0008_140a);
+    }
+
+    #[test]
+    fn datagram_matches_driver_format() {
+        // GCONF and IOIN read requests
+        assert_eq!(crc8(&[0x05, 0x00, 0x00]), 0x48);
+        assert_eq!(crc8(&[0x05, 0x00, 0x06]), 0x6f);
+        let datagram = write_datagram(2, IHOLD_IRUN, BASELINE.to_bits());
+        assert_eq!(&datagram[..7], &[0x05, 0x02, 0x90, 0x00, 0x08, 0x14, 0x0a]);
+        assert_eq!(
+            parse_write_datagram(&datagram),
+            Some((2, IHOLD_IRUN, BASELINE.to_bits()))
+        );
+
+        let mut corrupted = datagram;
+        corrupted[5] ^= 1;
+        assert_eq!(parse_write_datagram(&corrupted), None);
+        // Read requests are not writes
+        assert_eq!(parse_write_datagram(&[0x05, 0x00, 0x06, 0x6f]), None);
+        assert_eq!(
+            parse_read_request(&[0x05, 0x00, 0x06, 0x6f]),
+            Some((0, 0x06))
+        );
+        assert_eq!(parse_read_request(&[0x05, 0x00, 0x06, 0x6e]), None);
+        assert_eq!(parse_read_request(&datagram), None);
+    }
+
+    #[test]
+    fn hides_writes_from_interface_counter() {
+        let mut reply = [0x05, 0xff, IFCNT, 0x00, 0x00, 0x00, 0x02, 0x00];
+        reply[7] = crc8(&reply[..7]);
+        assert!(hide_writes(&mut reply, 5));
+        assert_eq!(&reply[..7], &[0x05, 0xff, IFCNT, 0x00, 0x00, 0x00, 0xfd]);
+        assert_eq!(reply[7], crc8(&reply[..7]));
+
+        // Anything but an intact IFCNT reply goes through untouched
+        let mut other = [0x05, 0xff, IHOLD_IRUN, 0x00, 0x00, 0x00, 0x02, 0x00];
+        other[7] = crc8(&other[..7]);
+        let unchanged = other;
+        assert!(!hide_writes(&mut other, 5));
+        assert_eq!(other, unchanged);
+        reply[7] ^= 1;
+        let unchanged = reply;
+        assert!(!hide_writes(&mut reply, 5));
+        assert_eq!(reply, unchanged);
+    }
+
+    #[test]
+    fn scales_with_following_error() {
+        let policy = policy();
+        assert_eq!(policy.scale(0), 0.5);
+        assert_eq!(policy.scale(-4), 0.5);
+        assert_eq!(policy.scale(14), 1.0);
+        assert_eq!(policy.scale(-100), 1.5);
+        assert_eq!(BASELINE.scaled(2.0).irun, 31);
+    }
+
+    #[test]
+    fn boosts_immediately_and_lowers_slowly() {
+        let mut policy = policy();
+        assert_eq!(policy.update(0).map(|current| current.irun), Some(10));
+        assert_eq!(policy.update(0), None);
+        assert_eq!(policy.update(30).map(|current| current.irun), Some(30));
+        assert_eq!(policy.update(0), None);
+        assert_eq!(policy.update(0), None);
+        assert_eq!(policy.update(0).map(|current| current.irun), Some(10));
+        assert_eq!(policy.applied().map(|current| current.ihold), Some(5));
+    }
+
+    #[test]
+    fn new_baseline_is_written_again() {
+        let mut policy = policy();
+        policy.update(0);
+        policy.set_baseline(IholdIrun {
+            irun: 16,
+            ..BASELINE
+        });
+        assert_eq!(policy.update(0).map(|current| current.irun), Some(8));
+    }
+}
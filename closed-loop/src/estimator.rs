@@ -0,0 +1,216 @@
+// Filter state is kept relative to an integer origin so f32 keeps its precision on long moves
+const REBASE_DISTANCE: f32 = 65536.;
+
+/// Alpha-beta-gamma filter gains.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Gains {
+    /// Position gain.
+    pub alpha: f32,
+    /// Velocity gain.
+    pub beta: f32,
+    /// Acceleration gain.
+    pub gamma: f32,
+}
+
+impl Gains {
+    /// Critically damped gains of a fading memory filter.
+    /// `theta` between 0 and 1 trades noise rejection (close to 1) for response time (close to 0).
+    pub fn fading_memory(theta: f32) -> Self {
+        let theta = theta.clamp(0., 0.999);
+        let one_minus = 1. - theta;
+        Self {
+            alpha: 1. - theta * theta * theta,
+            beta: 1.5 * (1. - theta * theta) * one_minus,
+            gamma: 0.5 * one_minus * one_minus * one_minus,
+        }
+    }
+}
+
+impl Default for Gains {
+    fn default() -> Self {
+        Self::fading_memory(0.8)
+    }
+}
+
+/// Filtered encoder state.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Estimate {
+    /// Position, in the unit of the samples.
+    pub position: f64,
+    /// Velocity, in units per second.
+    pub velocity: f32,
+    /// Acceleration, in units per second squared.
+    pub acceleration: f32,
+}
+
+/// Alpha-beta-gamma observer estimating position, velocity and acceleration from timestamped
+/// position samples.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Estimator {
+    gains: Gains,
+    tick_hz: u64,
+    origin: i64,
+    position: f32,
+    velocity: f32,
+    acceleration: f32,
+    last_timestamp: Option<u64>,
+}
+
+impl Estimator {
+    /// Create a new estimator for samples timestamped with a clock running at `tick_hz`.
+    pub fn new(gains: Gains, tick_hz: u64) -> Self {
+        Self {
+            gains,
+            tick_hz,
+            origin: 0,
+            position: 0.,
+            velocity: 0.,
+            acceleration: 0.,
+            last_timestamp: None,
+        }
+    }
+
+    /// Get the current gains.
+    pub fn gains(&self) -> Gains {
+        self.gains
+    }
+
+    /// Replace the current gains, the state is kept.
+    pub fn set_gains(&mut self, gains: Gains) {
+        self.gains = gains;
+    }
+
+    /// Forget the state, the next sample starts the estimate over at rest.
+    pub fn reset(&mut self) {
+        self.last_timestamp = None;
+    }
+
+    /// Current estimate.
+    pub fn estimate(&self) -> Estimate {
+        Estimate {
+            position: self.origin as f64 + self.position as f64,
+            velocity: self.velocity,
+            acceleration: self.acceleration,
+        }
+    }
+
+    /// Feed a position sample taken at `timestamp` ticks.
+    /// Samples with a timestamp at or before the previous one are ignored.
+    pub fn update(&mut self, position: i64, timestamp: u64) -> Estimate {
+        let Some(last_timestamp) = self.last_timestamp else {
+            self.origin = position;
+            self.position = 0.;
+            self.velocity = 0.;
+            self.acceleration = 0.;
+            self.last_timestamp = Some(timestamp);
+            return self.estimate();
+        };
+
+        if timestamp <= last_timestamp {
+            return self.estimate();
+        }
+        let dt = (timestamp - last_timestamp) as f32 / self.tick_hz as f32;
+        self.last_timestamp = Some(timestamp);
+
+        // Predict
+        let predicted_position =
+            self.position + self.velocity * dt + 0.5 * self.acceleration * dt * dt;
+        let predicted_velocity = self.velocity + self.acceleration * dt;
+
+        // Correct
+        let residual = (position - self.origin) as f32 - predicted_position;
+        self.position = predicted_position + self.gains.alpha * residual;
+        self.velocity = predicted_velocity + self.gains.beta * residual / dt;
+        self.acceleration += 2. * self.gains.gamma * residual / (dt * dt);
+
+        if self.position.abs() > REBASE_DISTANCE {
+            let shift = self.position as i64;
+            self.origin += shift;
+            self.position -= shift as f32;
+        }
+
+        self.estimate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Estimator, Gains};
+
+    const TICK_HZ: u64 = 1_000_000;
+    // 100Hz samples
+    const SAMPLE_TICKS: u64 = 10_000;
+
+    fn close(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() <= tolerance
+    }
+
+    #[test]
+    fn gains_are_stable() {
+        for theta in [0., 0.25, 0.5, 0.8, 0.95] {
+            let gains = Gains::fading_memory(theta);
+            assert!(gains.alpha > 0. && gains.alpha <= 1.);
+            assert!(gains.beta >= 0. && gains.beta < 2.);
+            assert!(gains.gamma >= 0. && gains.gamma < 1.);
+        }
+    }
+
+    #[test]
+    fn holds_still() {
+        let mut estimator = Estimator::new(Gains::default(), TICK_HZ);
+        let mut estimate = Default::default();
+        for sample in 0..100 {
+            estimate = estimator.update(1234, sample * SAMPLE_TICKS);
+        }
+        assert!(close(estimate.position, 1234., 1e-3));
+        assert!(close(estimate.velocity as f64, 0., 1e-3));
+    }
+
+    #[test]
+    fn tracks_constant_velocity() {
+        // 4096 ticks per second
+        let mut estimator = Estimator::new(Gains::default(), TICK_HZ);
+        let mut estimate = Default::default();
+        for sample in 0..500u64 {
+            let position = (sample as f64 * 40.96) as i64;
+            estimate = estimator.update(position, sample * SAMPLE_TICKS);
+        }
+        assert!(close(estimate.velocity as f64, 4096., 40.));
+        assert!(close(estimate.acceleration as f64, 0., 100.));
+        assert!(close(estimate.position, 499. * 40.96, 1.));
+    }
+
+    #[test]
+    fn tracks_constant_acceleration() {
+        // 20000 ticks per second squared, lands on whole ticks at every sample
+        let mut estimator = Estimator::new(Gains::fading_memory(0.6), TICK_HZ);
+        let mut estimate = Default::default();
+        for sample in 0..400u64 {
+            let t = sample as f64 / 100.;
+            let position = (0.5 * 20000. * t * t) as i64;
+            estimate = estimator.update(position, sample * SAMPLE_TICKS);
+        }
+        assert!(close(estimate.acceleration as f64, 20000., 100.));
+        assert!(close(estimate.velocity as f64, 79800., 50.));
+    }
+
+    #[test]
+    fn keeps_precision_far_from_zero() {
+        let mut estimator = Estimator::new(Gains::default(), TICK_HZ);
+        let base = 1i64 << 40;
+        let mut estimate = Default::default();
+        for sample in 0..2000u64 {
+            estimate = estimator.update(base + sample as i64 * 500, sample * SAMPLE_TICKS);
+        }
+        assert!(close(estimate.position, (base + 1999 * 500) as f64, 1.));
+        assert!(close(estimate.velocity as f64, 50_000., 10.));
+    }
+
+    #[test]
+    fn ignores_stale_samples() {
+        let mut estimator = Estimator::new(Gains::default(), TICK_HZ);
+        estimator.update(0, 1000);
+        let estimate = estimator.update(100, 1000);
+        assert!(close(estimate.position, 0., 1e-6));
+    }
+}
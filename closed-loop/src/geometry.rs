@@ -0,0 +1,255 @@
+/// Geometry errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// Full steps per revolution of 0.
+    FullSteps,
+    /// Microsteps that are not a power of two up to 256.
+    Microsteps(u16),
+    /// Encoder resolution of 0.
+    Resolution,
+    /// The encoder did not move.
+    NoTravel,
+    /// The measured microsteps per revolution are not a whole number of electrical periods at
+    /// these microsteps, the motor lost steps or the microsteps are wrong.
+    StepsPerRev(u32),
+}
+
+// Full steps of a hybrid stepper come in whole electrical periods
+const PERIOD_FULL_STEPS: u32 = 4;
+
+// Encoder ticks the measured travel can be off by from noise and quantization
+const TRAVEL_TOLERANCE: f32 = 4.;
+
+/// Relation between motor steps and encoder ticks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EncoderGeometry {
+    full_steps: u16,
+    microsteps: u16,
+    resolution: u16,
+    inverted: bool,
+}
+
+impl EncoderGeometry {
+    /// 1.8° motor at 16 microsteps with a 12 bit encoder.
+    pub const DEFAULT: Self = Self {
+        full_steps: 200,
+        microsteps: 16,
+        resolution: 4096,
+        inverted: false,
+    };
+
+    /// Create a new geometry for a motor of `full_steps` per revolution driven at `microsteps`,
+    /// read by an encoder of `resolution` ticks per revolution.
+    /// `inverted` is set when the encoder counts down while the motor steps forward.
+    pub fn new(
+        full_steps: u16,
+        microsteps: u16,
+        resolution: u16,
+        inverted: bool,
+    ) -> Result<Self, Error> {
+        if full_steps == 0 {
+            return Err(Error::FullSteps);
+        }
+        if !microsteps.is_power_of_two() || microsteps > 256 {
+            return Err(Error::Microsteps(microsteps));
+        }
+        if resolution == 0 {
+            return Err(Error::Resolution);
+        }
+
+        Ok(Self {
+            full_steps,
+            microsteps,
+            resolution,
+            inverted,
+        })
+    }
+
+    /// Infer the geometry from the encoder `travel`, in ticks, over a move of `steps` microsteps
+    /// forward. The full steps per revolution are rounded to a whole number of electrical periods.
+    pub fn from_travel(
+        steps: u32,
+        travel: i64,
+        microsteps: u16,
+        resolution: u16,
+    ) -> Result<Self, Error> {
+        if !microsteps.is_power_of_two() || microsteps > 256 {
+            return Err(Error::Microsteps(microsteps));
+        }
+        if resolution == 0 {
+            return Err(Error::Resolution);
+        }
+        if travel == 0 {
+            return Err(Error::NoTravel);
+        }
+
+        let steps_per_rev = steps as f32 * resolution as f32 / travel.unsigned_abs() as f32;
+        let full_steps = steps_per_rev / microsteps as f32;
+        let periods = (full_steps / PERIOD_FULL_STEPS as f32 + 0.5) as u32;
+        let rounded = periods * PERIOD_FULL_STEPS;
+        // Full steps the measurement can be off by from a few ticks of travel
+        let tolerance = full_steps * TRAVEL_TOLERANCE / travel.unsigned_abs() as f32;
+        let deviation = full_steps - rounded as f32;
+        if rounded == 0
+            || rounded > u16::MAX as u32
+            || !(-tolerance..=tolerance).contains(&deviation)
+        {
+            return Err(Error::StepsPerRev(steps_per_rev as u32));
+        }
+
+        Self::new(rounded as u16, microsteps, resolution, travel < 0)
+    }
+
+    /// Full steps per revolution.
+    pub fn full_steps(&self) -> u16 {
+        self.full_steps
+    }
+
+    /// Microsteps per full step.
+    pub fn microsteps(&self) -> u16 {
+        self.microsteps
+    }
+
+    /// Encoder ticks per revolution.
+    pub fn resolution(&self) -> u16 {
+        self.resolution
+    }
+
+    /// Whether the encoder counts down while the motor steps forward.
+    pub fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Microsteps per revolution.
+    pub fn steps_per_rev(&self) -> u32 {
+        self.full_steps as u32 * self.microsteps as u32
+    }
+
+    /// Convert a distance in encoder ticks to microsteps, rounded towards zero.
+    pub fn ticks_to_steps(&self, ticks: i64) -> i64 {
+        let steps = ticks * self.steps_per_rev() as i64 / self.resolution as i64;
+        if self.inverted {
+            -steps
+        } else {
+            steps
+        }
+    }
+
+    /// Convert a distance in microsteps to encoder ticks, rounded towards zero.
+    pub fn steps_to_ticks(&self, steps: i64) -> i64 {
+        let ticks = steps * self.resolution as i64 / self.steps_per_rev() as i64;
+        if self.inverted {
+            -ticks
+        } else {
+            ticks
+        }
+    }
+
+    /// Degrees per microstep.
+    pub fn degrees_per_step(&self) -> f32 {
+        360. / self.steps_per_rev() as f32
+    }
+
+    /// Degrees per encoder tick.
+    pub fn degrees_per_tick(&self) -> f32 {
+        360. / self.resolution as f32
+    }
+}
+
+impl Default for EncoderGeometry {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EncoderGeometry, Error};
+
+    #[test]
+    fn converts_between_units() {
+        // 0.9° motor at 32 microsteps
+        let geometry = EncoderGeometry::new(400, 32, 4096, false).unwrap();
+        assert_eq!(geometry.steps_per_rev(), 12800);
+        assert_eq!(geometry.ticks_to_steps(4096), 12800);
+        assert_eq!(geometry.ticks_to_steps(-1024), -3200);
+        assert_eq!(geometry.steps_to_ticks(12800 * 3), 4096 * 3);
+        assert_eq!(geometry.steps_to_ticks(3), 0);
+    }
+
+    #[test]
+    fn inverted_encoder_flips_sign() {
+        let geometry = EncoderGeometry::new(200, 16, 4096, true).unwrap();
+        assert_eq!(geometry.ticks_to_steps(-4096), 3200);
+        assert_eq!(geometry.steps_to_ticks(1600), -2048);
+    }
+
+    #[test]
+    fn default_is_valid() {
+        let default = EncoderGeometry::default();
+        assert_eq!(
+            EncoderGeometry::new(
+                default.full_steps(),
+                default.microsteps(),
+                default.resolution(),
+                default.inverted()
+            ),
+            Ok(default)
+        );
+        assert_eq!(default.steps_per_rev(), 3200);
+    }
+
+    #[test]
+    fn rejects_invalid_geometry() {
+        assert_eq!(
+            EncoderGeometry::new(0, 16, 4096, false),
+            Err(Error::FullSteps)
+        );
+        assert_eq!(
+            EncoderGeometry::new(200, 12, 4096, false),
+            Err(Error::Microsteps(12))
+        );
+        assert_eq!(
+            EncoderGeometry::new(200, 512, 4096, false),
+            Err(Error::Microsteps(512))
+        );
+        assert_eq!(
+            EncoderGeometry::new(200, 16, 0, false),
+            Err(Error::Resolution)
+        );
+    }
+
+    #[test]
+    fn infers_geometry_from_travel() {
+        assert_eq!(
+            EncoderGeometry::from_travel(3200, 4096, 16, 4096),
+            EncoderGeometry::new(200, 16, 4096, false)
+        );
+        assert_eq!(
+            EncoderGeometry::from_travel(3200, -4093, 16, 4096),
+            EncoderGeometry::new(200, 16, 4096, true)
+        );
+        // 0.9° motor over half a turn
+        assert_eq!(
+            EncoderGeometry::from_travel(6400, 2050, 32, 4096),
+            EncoderGeometry::new(400, 32, 4096, false)
+        );
+    }
+
+    #[test]
+    fn rejects_inconsistent_travel() {
+        assert_eq!(
+            EncoderGeometry::from_travel(3200, 0, 16, 4096),
+            Err(Error::NoTravel)
+        );
+        // Lost steps along the way
+        assert_eq!(
+            EncoderGeometry::from_travel(3200, 3000, 16, 4096),
+            Err(Error::StepsPerRev(4369))
+        );
+        assert_eq!(
+            EncoderGeometry::from_travel(3200, 4096, 12, 4096),
+            Err(Error::Microsteps(12))
+        );
+    }
+}
@@ -0,0 +1,335 @@
+/// Magnet field as reported by the sensor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MagnetState {
+    /// No magnet detected.
+    Missing = 0,
+    /// Magnet detected, field within range.
+    Detected = 1,
+    /// Field too weak, magnet too far.
+    TooWeak = 2,
+    /// Field too strong, magnet too close.
+    TooStrong = 3,
+    /// The sensor does not report its field, magnet checks are skipped.
+    Unknown = 4,
+}
+
+impl From<MagnetState> for u8 {
+    fn from(state: MagnetState) -> Self {
+        state as Self
+    }
+}
+
+/// Reason an encoder is considered unhealthy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Fault {
+    /// The sensor could not be read.
+    Unreadable = 1,
+    /// No magnet detected.
+    MagnetMissing = 2,
+    /// Field too weak.
+    MagnetTooWeak = 3,
+    /// Field too strong.
+    MagnetTooStrong = 4,
+    /// Automatic gain control ran into one of its limits.
+    GainSaturated = 5,
+    /// Field magnitude outside of the configured range.
+    Magnitude = 6,
+}
+
+impl From<Fault> for u8 {
+    fn from(fault: Fault) -> Self {
+        fault as Self
+    }
+}
+
+/// Health readings of the sensor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HealthSample {
+    /// Magnet detection.
+    pub magnet: MagnetState,
+    /// Automatic gain control value.
+    pub gain: u8,
+    /// Field magnitude, in sensor units.
+    pub magnitude: u16,
+}
+
+/// Health monitoring settings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HealthLimits {
+    /// Gain at or below which the gain control is considered saturated.
+    pub gain_min: u8,
+    /// Gain at or above which the gain control is considered saturated.
+    pub gain_max: u8,
+    /// Lowest acceptable field magnitude.
+    pub magnitude_min: u16,
+    /// Highest acceptable field magnitude.
+    pub magnitude_max: u16,
+    /// Consecutive faulty samples before a warning is raised.
+    pub warn_samples: u8,
+    /// Consecutive faulty samples before a shutdown is requested, 0 never shuts down.
+    pub shutdown_samples: u8,
+}
+
+impl HealthLimits {
+    /// Only checks for magnet presence and range, shutting down after 10 faulty samples.
+    pub const DEFAULT: Self = Self {
+        gain_min: 0,
+        gain_max: u8::MAX,
+        magnitude_min: 0,
+        magnitude_max: u16::MAX,
+        warn_samples: 1,
+        shutdown_samples: 10,
+    };
+
+    /// Get the fault of a sample, if any. `None` stands for a sample that could not be read.
+    pub fn check(&self, sample: Option<HealthSample>) -> Option<Fault> {
+        let Some(sample) = sample else {
+            return Some(Fault::Unreadable);
+        };
+
+        match sample.magnet {
+            MagnetState::Missing => return Some(Fault::MagnetMissing),
+            MagnetState::TooWeak => return Some(Fault::MagnetTooWeak),
+            MagnetState::TooStrong => return Some(Fault::MagnetTooStrong),
+            MagnetState::Unknown => return None,
+            MagnetState::Detected => {}
+        }
+
+        if sample.gain <= self.gain_min || sample.gain >= self.gain_max {
+            Some(Fault::GainSaturated)
+        } else if sample.magnitude < self.magnitude_min || sample.magnitude > self.magnitude_max {
+            Some(Fault::Magnitude)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for HealthLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Change in encoder health worth reporting.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HealthEvent {
+    /// A fault persisted for the warning limit.
+    Warning(Fault),
+    /// A fault persisted for the shutdown limit.
+    Shutdown(Fault),
+    /// Samples are healthy again after a warning.
+    Recovered,
+}
+
+/// Escalates persisting sensor faults from a warning to a shutdown.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HealthMonitor {
+    limits: HealthLimits,
+    fault: Option<Fault>,
+    faulty_samples: u16,
+    warned: bool,
+    shut_down: bool,
+}
+
+impl HealthMonitor {
+    /// Create a new monitor with the given settings.
+    pub const fn new(limits: HealthLimits) -> Self {
+        Self {
+            limits,
+            fault: None,
+            faulty_samples: 0,
+            warned: false,
+            shut_down: false,
+        }
+    }
+
+    /// Get the current settings.
+    pub fn limits(&self) -> HealthLimits {
+        self.limits
+    }
+
+    /// Replace the current settings, the fault history is kept.
+    pub fn set_limits(&mut self, limits: HealthLimits) {
+        self.limits = limits;
+    }
+
+    /// Fault of the latest sample.
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    /// Feed a new sample, `None` if the sensor could not be read.
+    /// Every event is only returned once per fault streak.
+    pub fn update(&mut self, sample: Option<HealthSample>) -> Option<HealthEvent> {
+        self.fault = self.limits.check(sample);
+
+        let Some(fault) = self.fault else {
+            return self.clear();
+        };
+
+        self.faulty_samples = self.faulty_samples.saturating_add(1);
+
+        let shutdown_samples = self.limits.shutdown_samples as u16;
+        if shutdown_samples != 0 && self.faulty_samples >= shutdown_samples && !self.shut_down {
+            self.shut_down = true;
+            self.warned = true;
+            return Some(HealthEvent::Shutdown(fault));
+        }
+
+        if self.faulty_samples >= self.limits.warn_samples.max(1) as u16 && !self.warned {
+            self.warned = true;
+            return Some(HealthEvent::Warning(fault));
+        }
+
+        None
+    }
+
+    /// Note a successful read that carries no health sample, like an angle reading. It ends a
+    /// streak of unreadable samples, other faults are left for the next sample to clear.
+    pub fn update_readable(&mut self) -> Option<HealthEvent> {
+        if self.fault != Some(Fault::Unreadable) {
+            return None;
+        }
+        self.fault = None;
+        self.clear()
+    }
+
+    fn clear(&mut self) -> Option<HealthEvent> {
+        self.faulty_samples = 0;
+        self.shut_down = false;
+        if core::mem::take(&mut self.warned) {
+            Some(HealthEvent::Recovered)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Fault, HealthEvent, HealthLimits, HealthMonitor, HealthSample, MagnetState};
+
+    const HEALTHY: HealthSample = HealthSample {
+        magnet: MagnetState::Detected,
+        gain: 64,
+        magnitude: 2000,
+    };
+
+    fn limits() -> HealthLimits {
+        HealthLimits {
+            gain_min: 0,
+            gain_max: 128,
+            magnitude_min: 1000,
+            magnitude_max: 3000,
+            warn_samples: 2,
+            shutdown_samples: 4,
+        }
+    }
+
+    #[test]
+    fn finds_faults() {
+        let limits = limits();
+        assert_eq!(limits.check(Some(HEALTHY)), None);
+        assert_eq!(limits.check(None), Some(Fault::Unreadable));
+        let missing = HealthSample {
+            magnet: MagnetState::Missing,
+            ..HEALTHY
+        };
+        assert_eq!(limits.check(Some(missing)), Some(Fault::MagnetMissing));
+        let saturated = HealthSample {
+            gain: 128,
+            ..HEALTHY
+        };
+        assert_eq!(limits.check(Some(saturated)), Some(Fault::GainSaturated));
+        let weak = HealthSample {
+            magnitude: 999,
+            ..HEALTHY
+        };
+        assert_eq!(limits.check(Some(weak)), Some(Fault::Magnitude));
+    }
+
+    #[test]
+    fn skips_unknown_magnet() {
+        let unknown = HealthSample {
+            magnet: MagnetState::Unknown,
+            gain: 128,
+            magnitude: 0,
+        };
+        assert_eq!(limits().check(Some(unknown)), None);
+        assert_eq!(limits().check(None), Some(Fault::Unreadable));
+    }
+
+    #[test]
+    fn escalates_persisting_faults() {
+        let mut monitor = HealthMonitor::new(limits());
+        let far = Some(HealthSample {
+            magnet: MagnetState::TooWeak,
+            ..HEALTHY
+        });
+        assert_eq!(monitor.update(far), None);
+        assert_eq!(
+            monitor.update(far),
+            Some(HealthEvent::Warning(Fault::MagnetTooWeak))
+        );
+        assert_eq!(monitor.update(None), None);
+        assert_eq!(
+            monitor.update(None),
+            Some(HealthEvent::Shutdown(Fault::Unreadable))
+        );
+        assert_eq!(monitor.update(None), None);
+        assert_eq!(monitor.fault(), Some(Fault::Unreadable));
+    }
+
+    #[test]
+    fn reports_recovery_after_warning() {
+        let mut monitor = HealthMonitor::new(limits());
+        monitor.update(None);
+        monitor.update(None);
+        assert_eq!(monitor.update(Some(HEALTHY)), Some(HealthEvent::Recovered));
+        assert_eq!(monitor.update(Some(HEALTHY)), None);
+        assert_eq!(monitor.fault(), None);
+    }
+
+    #[test]
+    fn glitches_are_not_reported() {
+        let mut monitor = HealthMonitor::new(limits());
+        for _ in 0..10 {
+            assert_eq!(monitor.update(None), None);
+            assert_eq!(monitor.update(Some(HEALTHY)), None);
+        }
+    }
+
+    #[test]
+    fn readable_only_clears_unreadable() {
+        let mut monitor = HealthMonitor::new(limits());
+        monitor.update(None);
+        monitor.update(None);
+        assert_eq!(monitor.update_readable(), Some(HealthEvent::Recovered));
+        assert_eq!(monitor.fault(), None);
+        assert_eq!(monitor.update(None), None);
+
+        let missing = Some(HealthSample {
+            magnet: MagnetState::Missing,
+            ..HEALTHY
+        });
+        monitor.update(missing);
+        assert_eq!(monitor.update_readable(), None);
+        assert_eq!(monitor.fault(), Some(Fault::MagnetMissing));
+    }
+
+    #[test]
+    fn shutdown_can_be_disabled() {
+        let mut monitor = HealthMonitor::new(HealthLimits {
+            shutdown_samples: 0,
+            ..limits()
+        });
+        for _ in 0..100 {
+            if let Some(event) = monitor.update(None) {
+                assert_eq!(event, HealthEvent::Warning(Fault::Unreadable));
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+/// Idle hold errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// Value does not map to a [`HoldAction`].
+    InvalidAction(u8),
+}
+
+/// Reaction to the motor being moved away from its commanded position while idle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum HoldAction {
+    /// Report the disturbance, the position is left where it was pushed to.
+    Report = 0,
+    /// Report the disturbance and step back to the commanded position.
+    Restore = 1,
+}
+
+impl TryFrom<u8> for HoldAction {
+    type Error = Error;
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            0 => Ok(Self::Report),
+            1 => Ok(Self::Restore),
+            _ => Err(Error::InvalidAction(byte)),
+        }
+    }
+}
+
+impl From<HoldAction> for u8 {
+    fn from(action: HoldAction) -> Self {
+        action as Self
+    }
+}
+
+/// Idle hold settings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HoldConfig {
+    /// Following error, in steps, tolerated before the motor is considered disturbed.
+    pub threshold: u32,
+    /// Reaction to a disturbance.
+    pub action: HoldAction,
+    /// Maximum number of steps issued per update when restoring.
+    pub max_steps: u32,
+}
+
+impl Default for HoldConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 4,
+            action: HoldAction::Report,
+            max_steps: 16,
+        }
+    }
+}
+
+/// Change of the idle hold state.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HoldEvent {
+    /// The motor was moved away by this following error, in steps.
+    Disturbed(i32),
+    /// The motor is back at its commanded position.
+    Settled,
+}
+
+/// Watches the following error of an idle motor and restores its position when configured to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IdleHold {
+    config: HoldConfig,
+    disturbed: bool,
+}
+
+impl IdleHold {
+    /// Create a new hold with the given settings.
+    pub const fn new(config: HoldConfig) -> Self {
+        Self {
+            config,
+            disturbed: false,
+        }
+    }
+
+    /// Get the current settings.
+    pub fn config(&self) -> HoldConfig {
+        self.config
+    }
+
+    /// Replace the current settings, this re-arms the hold if they changed.
+    pub fn set_config(&mut self, config: HoldConfig) {
+        if self.config != config {
+            *self = Self::new(config);
+        }
+    }
+
+    /// Whether a disturbance has been reported and the motor has not settled since.
+    pub fn is_disturbed(&self) -> bool {
+        self.disturbed
+    }
+
+    /// Feed a following error sample, in steps.
+    /// Moving samples re-arm the hold, the commanded position is only held while `idle`.
+    pub fn update(&mut self, following_error: i32, idle: bool) -> Option<HoldEvent> {
+        if !idle {
+            self.disturbed = false;
+            return None;
+        }
+
+        let magnitude = following_error.unsigned_abs();
+        if !self.disturbed && magnitude > self.config.threshold {
+            self.disturbed = true;
+            return Some(HoldEvent::Disturbed(following_error));
+        }
+
+        // Restoring goes all the way back, reporting only needs the error back within the threshold
+        let settled = match self.config.action {
+            HoldAction::Report => magnitude <= self.config.threshold,
+            HoldAction::Restore => magnitude == 0,
+        };
+        if self.disturbed && settled {
+            self.disturbed = false;
+            return Some(HoldEvent::Settled);
+        }
+
+        None
+    }
+
+    /// Get the steps bringing the motor back to its commanded position, 0 unless restoring.
+    /// A positive result means the motor has to step forward.
+    pub fn restore_steps(&self, following_error: i32) -> i32 {
+        if !self.disturbed || self.config.action != HoldAction::Restore {
+            return 0;
+        }
+        let steps = following_error.unsigned_abs().min(self.config.max_steps) as i32;
+        if following_error.is_positive() {
+            -steps
+        } else {
+            steps
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HoldAction, HoldConfig, HoldEvent, IdleHold};
+
+    fn hold(action: HoldAction) -> IdleHold {
+        IdleHold::new(HoldConfig {
+            threshold: 5,
+            action,
+            max_steps: 8,
+        })
+    }
+
+    #[test]
+    fn action_to_byte_roundtrip() {
+        for byte in 0..=u8::MAX {
+            if let Ok(action) = HoldAction::try_from(byte) {
+                assert_eq!(u8::from(action), byte);
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_error_while_moving() {
+        let mut hold = hold(HoldAction::Restore);
+        assert_eq!(hold.update(50, false), None);
+        assert!(!hold.is_disturbed());
+        assert_eq!(hold.restore_steps(50), 0);
+    }
+
+    #[test]
+    fn reports_once_until_settled() {
+        let mut hold = hold(HoldAction::Report);
+        assert_eq!(hold.update(3, true), None);
+        assert_eq!(hold.update(-20, true), Some(HoldEvent::Disturbed(-20)));
+        assert_eq!(hold.update(-30, true), None);
+        assert_eq!(hold.restore_steps(-30), 0);
+        assert_eq!(hold.update(-4, true), Some(HoldEvent::Settled));
+        assert!(!hold.is_disturbed());
+    }
+
+    #[test]
+    fn restores_commanded_position() {
+        let mut hold = hold(HoldAction::Restore);
+        assert_eq!(hold.update(20, true), Some(HoldEvent::Disturbed(20)));
+        assert_eq!(hold.restore_steps(20), -8);
+        assert_eq!(hold.update(12, true), None);
+        assert_eq!(hold.restore_steps(12), -8);
+        // Within the threshold, but not back yet
+        assert_eq!(hold.update(-3, true), None);
+        assert_eq!(hold.restore_steps(-3), 3);
+        assert_eq!(hold.update(0, true), Some(HoldEvent::Settled));
+        assert_eq!(hold.restore_steps(0), 0);
+    }
+
+    #[test]
+    fn moving_rearms() {
+        let mut hold = hold(HoldAction::Restore);
+        hold.update(20, true);
+        assert!(hold.is_disturbed());
+        assert_eq!(hold.update(20, false), None);
+        assert!(!hold.is_disturbed());
+        assert_eq!(hold.update(20, true), Some(HoldEvent::Disturbed(20)));
+    }
+}
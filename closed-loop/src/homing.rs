@@ -0,0 +1,116 @@
+use crate::geometry::EncoderGeometry;
+
+/// Homing refinement errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The closest target is this many steps away, more than the refinement may move.
+    TooFar(i32),
+}
+
+/// Position the motor is moved to after the endstop triggered, so homing ends at the same encoder
+/// angle every time rather than wherever the switch happened to trigger.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RefinementConfig {
+    /// Encoder angle to move to, in ticks.
+    pub angle: u16,
+    /// Full steps after which the target comes around again, 0 for once per revolution.
+    /// With 1 the motor moves to the closest full step, 4 keeps the same electrical phase.
+    pub period: u16,
+    /// Largest move allowed, in microsteps.
+    pub max_steps: u32,
+}
+
+impl Default for RefinementConfig {
+    fn default() -> Self {
+        Self {
+            angle: 0,
+            period: 1,
+            max_steps: 64,
+        }
+    }
+}
+
+impl RefinementConfig {
+    /// Microsteps from the encoder `angle` to the closest target, rounded to the closest microstep.
+    pub fn steps(&self, geometry: &EncoderGeometry, angle: u16) -> Result<i32, Error> {
+        let resolution = geometry.resolution() as i64;
+        let steps_per_rev = geometry.steps_per_rev() as i64;
+        let period = if self.period == 0 {
+            steps_per_rev
+        } else {
+            self.period as i64 * geometry.microsteps() as i64
+        };
+
+        // Worked out in steps times resolution to stay exact until the final rounding
+        let mut distance = (self.angle as i64 - angle as i64) * steps_per_rev;
+        if geometry.inverted() {
+            distance = -distance;
+        }
+        let period = period * resolution;
+        let distance = (distance + period / 2).rem_euclid(period) - period / 2;
+        let steps = (distance + resolution / 2).div_euclid(resolution) as i32;
+
+        if steps.unsigned_abs() > self.max_steps {
+            return Err(Error::TooFar(steps));
+        }
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, RefinementConfig};
+    use crate::geometry::EncoderGeometry;
+
+    fn per_rev(angle: u16) -> RefinementConfig {
+        RefinementConfig {
+            angle,
+            period: 0,
+            max_steps: 3200,
+        }
+    }
+
+    #[test]
+    fn moves_to_angle() {
+        let geometry = EncoderGeometry::DEFAULT;
+        // 4096 ticks over 3200 steps
+        assert_eq!(per_rev(1024).steps(&geometry, 1000), Ok(19));
+        assert_eq!(per_rev(1000).steps(&geometry, 1024), Ok(-19));
+        assert_eq!(per_rev(1000).steps(&geometry, 1000), Ok(0));
+        // Across the zero of the encoder
+        assert_eq!(per_rev(10).steps(&geometry, 4090), Ok(13));
+    }
+
+    #[test]
+    fn inverted_encoder_moves_the_other_way() {
+        let geometry = EncoderGeometry::new(200, 16, 4096, true).unwrap();
+        assert_eq!(per_rev(1024).steps(&geometry, 1000), Ok(-19));
+    }
+
+    #[test]
+    fn moves_to_closest_period() {
+        let geometry = EncoderGeometry::DEFAULT;
+        let full_step = RefinementConfig {
+            angle: 0,
+            period: 1,
+            max_steps: 16,
+        };
+        // One full step is 20.48 ticks
+        assert_eq!(full_step.steps(&geometry, 2048), Ok(0));
+        assert_eq!(full_step.steps(&geometry, 2050), Ok(-2));
+        assert_eq!(full_step.steps(&geometry, 2046), Ok(2));
+        assert_eq!(full_step.steps(&geometry, 2058), Ok(-8));
+        // Closer to the next full step
+        assert_eq!(full_step.steps(&geometry, 2060), Ok(7));
+    }
+
+    #[test]
+    fn limits_the_move() {
+        let geometry = EncoderGeometry::DEFAULT;
+        let config = RefinementConfig {
+            max_steps: 16,
+            ..per_rev(1024)
+        };
+        assert_eq!(config.steps(&geometry, 1000), Err(Error::TooFar(19)));
+    }
+}
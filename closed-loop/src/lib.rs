@@ -0,0 +1,36 @@
+#![no_std]
+
+/// Correction gains from a test move.
+pub mod autotune;
+/// Direction reversal lost motion.
+pub mod backlash;
+/// Encoder linearization.
+pub mod calibration;
+/// Following error correction.
+pub mod correction;
+/// Adaptive motor current.
+pub mod current;
+/// Position, velocity and acceleration estimation.
+pub mod estimator;
+/// Conversion between motor steps and encoder ticks.
+pub mod geometry;
+/// Sensor health monitoring.
+pub mod health;
+/// Idle position hold.
+pub mod hold;
+/// Homing to a repeatable encoder angle.
+pub mod homing;
+/// Persistent settings record.
+pub mod settings;
+/// Simulated motor and encoder.
+pub mod sim;
+/// Frequency analysis.
+pub mod spectrum;
+/// Skipped step detection.
+pub mod stall;
+/// Following error statistics.
+pub mod stats;
+#[cfg(test)]
+mod test_sim;
+/// Encoder angle ranges.
+pub mod window;
@@ -0,0 +1,503 @@
+use crate::{
+    calibration::{self, CalibrationTable},
+    correction::CorrectionConfig,
+    estimator::Gains,
+    geometry::{self, EncoderGeometry},
+    health::HealthLimits,
+    stall::{self, StallAction, StallConfig},
+};
+
+/// Largest encoded size of a settings record.
+pub const MAX_SETTINGS_SIZE: usize = 1024;
+
+// "CLST" when read as bytes
+const MAGIC: u32 = 0x5453_4C43;
+const VERSION: u16 = 1;
+// Magic, version and payload length
+const HEADER_SIZE: usize = 8;
+const CRC_SIZE: usize = 4;
+
+const HAS_CALIBRATION: u8 = 1 << 0;
+const HAS_CORRECTION: u8 = 1 << 1;
+const HAS_STALL: u8 = 1 << 2;
+const HAS_ESTIMATOR: u8 = 1 << 3;
+const HAS_TRACKING: u8 = 1 << 4;
+const HAS_HEALTH: u8 = 1 << 5;
+
+/// Settings record errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// Buffer too small to hold the record.
+    BufferTooSmall,
+
+    /// The storage holds erased memory, nothing was ever saved.
+    Erased,
+
+    /// Unknown magic number, the storage holds something else.
+    Magic(u32),
+
+    /// Record written by an unsupported version.
+    Version(u16),
+
+    /// Payload length does not match its content.
+    Length(u16),
+
+    /// The record is corrupted.
+    Crc {
+        /// CRC stored in the record.
+        stored: u32,
+        /// CRC computed over the record.
+        computed: u32,
+    },
+
+    /// Invalid geometry.
+    Geometry(geometry::Error),
+
+    /// Invalid calibration table.
+    Calibration(calibration::Error),
+
+    /// Invalid stall detection settings.
+    Stall(stall::Error),
+}
+
+/// Closed loop correction settings.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CorrectionSettings {
+    /// Correction settings.
+    pub correction: CorrectionConfig,
+    /// Ticks between corrective steps.
+    pub step_interval: u32,
+}
+
+/// Stall detection settings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StallSettings {
+    /// Stall detection settings.
+    pub stall: StallConfig,
+    /// Reason reported when the stall triggers the active trsync.
+    pub trigger_reason: u8,
+}
+
+/// Health monitoring settings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HealthSettings {
+    /// Ticks between health checks.
+    pub interval: u32,
+    /// Health limits.
+    pub limits: HealthLimits,
+}
+
+/// Everything needed to restore the encoder setup after a reset, `None` entries were never
+/// configured.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Settings {
+    /// Motor and encoder geometry.
+    pub geometry: EncoderGeometry,
+    /// Encoder linearization.
+    pub calibration: Option<CalibrationTable>,
+    /// Following error correction.
+    pub correction: Option<CorrectionSettings>,
+    /// Stall detection.
+    pub stall: Option<StallSettings>,
+    /// Estimator gains.
+    pub estimator: Option<Gains>,
+    /// Maximum velocity for the multi-turn tracking, in encoder ticks per second.
+    pub tracking_max_velocity: Option<u32>,
+    /// Health monitoring.
+    pub health: Option<HealthSettings>,
+}
+
+impl Settings {
+    /// Serialize into `buffer`, returns the number of bytes written.
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let mut writer = Writer::new(buffer);
+        // Payload length is filled in once known
+        writer.u32(MAGIC)?;
+        writer.u16(VERSION)?;
+        writer.u16(0)?;
+
+        writer.u16(self.geometry.full_steps())?;
+        writer.u16(self.geometry.microsteps())?;
+        writer.u16(self.geometry.resolution())?;
+        writer.u8(self.geometry.inverted() as u8)?;
+
+        let flags = flag(self.calibration.is_some(), HAS_CALIBRATION)
+            | flag(self.correction.is_some(), HAS_CORRECTION)
+            | flag(self.stall.is_some(), HAS_STALL)
+            | flag(self.estimator.is_some(), HAS_ESTIMATOR)
+            | flag(self.tracking_max_velocity.is_some(), HAS_TRACKING)
+            | flag(self.health.is_some(), HAS_HEALTH);
+        writer.u8(flags)?;
+
+        if let Some(correction) = &self.correction {
+            writer.u32(correction.correction.band)?;
+            writer.f32(correction.correction.gain)?;
+            writer.u32(correction.correction.max_steps)?;
+            writer.u32(correction.step_interval)?;
+        }
+        if let Some(stall) = &self.stall {
+            writer.u32(stall.stall.threshold)?;
+            writer.u8(stall.stall.samples)?;
+            writer.u8(stall.stall.action.into())?;
+            writer.u8(stall.trigger_reason)?;
+        }
+        if let Some(gains) = &self.estimator {
+            writer.f32(gains.alpha)?;
+            writer.f32(gains.beta)?;
+            writer.f32(gains.gamma)?;
+        }
+        if let Some(max_velocity) = self.tracking_max_velocity {
+            writer.u32(max_velocity)?;
+        }
+        if let Some(health) = &self.health {
+            writer.u32(health.interval)?;
+            writer.u8(health.limits.gain_min)?;
+            writer.u8(health.limits.gain_max)?;
+            writer.u16(health.limits.magnitude_min)?;
+            writer.u16(health.limits.magnitude_max)?;
+            writer.u8(health.limits.warn_samples)?;
+            writer.u8(health.limits.shutdown_samples)?;
+        }
+        if let Some(table) = &self.calibration {
+            writer.u16(table.resolution())?;
+            writer.u16(table.origin())?;
+            writer.u8(table.inverted() as u8)?;
+            writer.u16(table.points())?;
+            for offset in table.offsets() {
+                writer.u16(*offset)?;
+            }
+        }
+
+        let payload_length = (writer.position - HEADER_SIZE) as u16;
+        writer.buffer[6..HEADER_SIZE].copy_from_slice(&payload_length.to_le_bytes());
+        let crc = crc32(&writer.buffer[..writer.position]);
+        writer.u32(crc)?;
+
+        Ok(writer.position)
+    }
+
+    /// Deserialize a record written by [`Settings::encode`], trailing bytes are ignored.
+    pub fn decode(buffer: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(buffer);
+        let magic = reader.u32()?;
+        if magic == u32::MAX {
+            return Err(Error::Erased);
+        }
+        if magic != MAGIC {
+            return Err(Error::Magic(magic));
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(Error::Version(version));
+        }
+        let payload_length = reader.u16()?;
+
+        let record_length = HEADER_SIZE + payload_length as usize;
+        if buffer.len() < record_length + CRC_SIZE {
+            return Err(Error::Length(payload_length));
+        }
+        let stored = u32::from_le_bytes(
+            buffer[record_length..record_length + CRC_SIZE]
+                .try_into()
+                .unwrap(),
+        );
+        let computed = crc32(&buffer[..record_length]);
+        if stored != computed {
+            return Err(Error::Crc { stored, computed });
+        }
+
+        // Only read the payload from here on
+        let mut reader = Reader::new(&buffer[..record_length]);
+        reader.position = HEADER_SIZE;
+
+        let geometry = EncoderGeometry::new(
+            reader.u16()?,
+            reader.u16()?,
+            reader.u16()?,
+            reader.u8()? != 0,
+        )
+        .map_err(Error::Geometry)?;
+        let flags = reader.u8()?;
+
+        let mut settings = Settings {
+            geometry,
+            ..Default::default()
+        };
+
+        if flags & HAS_CORRECTION != 0 {
+            settings.correction = Some(CorrectionSettings {
+                correction: CorrectionConfig {
+                    band: reader.u32()?,
+                    gain: reader.f32()?,
+                    max_steps: reader.u32()?,
+                },
+                step_interval: reader.u32()?,
+            });
+        }
+        if flags & HAS_STALL != 0 {
+            settings.stall = Some(StallSettings {
+                stall: StallConfig {
+                    threshold: reader.u32()?,
+                    samples: reader.u8()?,
+                    action: StallAction::try_from(reader.u8()?).map_err(Error::Stall)?,
+                },
+                trigger_reason: reader.u8()?,
+            });
+        }
+        if flags & HAS_ESTIMATOR != 0 {
+            settings.estimator = Some(Gains {
+                alpha: reader.f32()?,
+                beta: reader.f32()?,
+                gamma: reader.f32()?,
+            });
+        }
+        if flags & HAS_TRACKING != 0 {
+            settings.tracking_max_velocity = Some(reader.u32()?);
+        }
+        if flags & HAS_HEALTH != 0 {
+            settings.health = Some(HealthSettings {
+                interval: reader.u32()?,
+                limits: HealthLimits {
+                    gain_min: reader.u8()?,
+                    gain_max: reader.u8()?,
+                    magnitude_min: reader.u16()?,
+                    magnitude_max: reader.u16()?,
+                    warn_samples: reader.u8()?,
+                    shutdown_samples: reader.u8()?,
+                },
+            });
+        }
+        if flags & HAS_CALIBRATION != 0 {
+            let resolution = reader.u16()?;
+            let origin = reader.u16()?;
+            let inverted = reader.u8()? != 0;
+            let points = reader.u16()? as usize;
+            let mut offsets = [0u16; calibration::MAX_CALIBRATION_POINTS];
+            let offsets = offsets
+                .get_mut(..points)
+                .ok_or(Error::Calibration(calibration::Error::PointCount(points)))?;
+            for offset in offsets.iter_mut() {
+                *offset = reader.u16()?;
+            }
+            settings.calibration = Some(
+                CalibrationTable::from_parts(resolution, origin, inverted, offsets)
+                    .map_err(Error::Calibration)?,
+            );
+        }
+
+        if reader.position != record_length {
+            return Err(Error::Length(payload_length));
+        }
+
+        Ok(settings)
+    }
+}
+
+fn flag(set: bool, flag: u8) -> u8 {
+    if set {
+        flag
+    } else {
+        0
+    }
+}
+
+/// CRC-32/ISO-HDLC, as used by zlib and Ethernet.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.position + bytes.len();
+        self.buffer
+            .get_mut(self.position..end)
+            .ok_or(Error::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.position = end;
+        Ok(())
+    }
+
+    fn u8(&mut self, value: u8) -> Result<(), Error> {
+        self.bytes(&[value])
+    }
+
+    fn u16(&mut self, value: u16) -> Result<(), Error> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn u32(&mut self, value: u32) -> Result<(), Error> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn f32(&mut self, value: f32) -> Result<(), Error> {
+        self.u32(value.to_bits())
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let end = self.position + N;
+        let bytes = self
+            .buffer
+            .get(self.position..end)
+            .ok_or(Error::BufferTooSmall)?;
+        self.position = end;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        crc32, CorrectionSettings, Error, HealthSettings, Settings, StallSettings,
+        MAX_SETTINGS_SIZE,
+    };
+    use crate::{
+        calibration::{CalibrationTable, MAX_CALIBRATION_POINTS},
+        correction::CorrectionConfig,
+        estimator::Gains,
+        geometry::EncoderGeometry,
+        health::HealthLimits,
+        stall::{StallAction, StallConfig},
+    };
+
+    fn full_settings() -> Settings {
+        let mut offsets = [0u16; MAX_CALIBRATION_POINTS];
+        for (index, offset) in offsets.iter_mut().enumerate() {
+            *offset = (index * 16 + index % 3) as u16;
+        }
+        Settings {
+            geometry: EncoderGeometry::new(400, 32, 4096, true).unwrap(),
+            calibration: Some(CalibrationTable::from_parts(4096, 1234, true, &offsets).unwrap()),
+            correction: Some(CorrectionSettings {
+                correction: CorrectionConfig {
+                    band: 3,
+                    gain: 0.75,
+                    max_steps: 8,
+                },
+                step_interval: 1600,
+            }),
+            stall: Some(StallSettings {
+                stall: StallConfig {
+                    threshold: 64,
+                    samples: 4,
+                    action: StallAction::Trigger,
+                },
+                trigger_reason: 4,
+            }),
+            estimator: Some(Gains::fading_memory(0.7)),
+            tracking_max_velocity: Some(200_000),
+            health: Some(HealthSettings {
+                interval: 1_600_000,
+                limits: HealthLimits {
+                    gain_min: 8,
+                    gain_max: 120,
+                    ..HealthLimits::DEFAULT
+                },
+            }),
+        }
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn roundtrips_full_settings() {
+        let settings = full_settings();
+        let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+        let length = settings.encode(&mut buffer).unwrap();
+        assert_eq!(Settings::decode(&buffer[..length]), Ok(settings));
+    }
+
+    #[test]
+    fn roundtrips_default_settings() {
+        let settings = Settings::default();
+        let mut buffer = [0xffu8; MAX_SETTINGS_SIZE];
+        let length = settings.encode(&mut buffer).unwrap();
+        // Trailing erased flash is ignored
+        assert_eq!(Settings::decode(&buffer), Ok(settings));
+        assert!(length < 32);
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+        let length = full_settings().encode(&mut buffer).unwrap();
+        buffer[length / 2] ^= 0x10;
+        assert!(matches!(
+            Settings::decode(&buffer[..length]),
+            Err(Error::Crc { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_foreign_records() {
+        assert_eq!(Settings::decode(&[0xff; 64]), Err(Error::Erased));
+        assert_eq!(Settings::decode(&[0; 64]), Err(Error::Magic(0)));
+
+        let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+        let length = Settings::default().encode(&mut buffer).unwrap();
+        buffer[4] = 2;
+        assert_eq!(Settings::decode(&buffer[..length]), Err(Error::Version(2)));
+        assert_eq!(Settings::decode(&buffer[..3]), Err(Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn reports_small_buffers() {
+        let mut buffer = [0u8; 64];
+        assert_eq!(
+            full_settings().encode(&mut buffer),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
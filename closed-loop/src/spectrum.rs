@@ -0,0 +1,264 @@
+/// Largest number of samples analyzed at once.
+pub const MAX_SAMPLES: usize = 1024;
+
+/// Spectrum errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// Sample count that is not a power of two from 4 to [`MAX_SAMPLES`], or buffers of different
+    /// lengths.
+    Length(usize),
+}
+
+/// Frequency standing out of the spectrum.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Peak {
+    /// Frequency, in Hz.
+    pub frequency: f32,
+    /// Amplitude of the oscillation at that frequency, in the unit of the samples.
+    pub amplitude: f32,
+}
+
+/// Fourier transform of the complex signal `re` + i·`im` in place.
+pub fn fft(re: &mut [f32], im: &mut [f32]) -> Result<(), Error> {
+    let n = check_length(re, im)?;
+
+    // Put the samples in bit reversed order so the butterflies can work in place
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        let (cos, sin) = unit_root(len);
+        for start in (0..n).step_by(len) {
+            // Twiddle factor e^(-2πik/len), turned a step further for each k
+            let (mut wr, mut wi) = (1., 0.);
+            for k in start..start + half {
+                let tr = re[k + half] * wr - im[k + half] * wi;
+                let ti = re[k + half] * wi + im[k + half] * wr;
+                re[k + half] = re[k] - tr;
+                im[k + half] = im[k] - ti;
+                re[k] += tr;
+                im[k] += ti;
+                (wr, wi) = (wr * cos + wi * sin, wi * cos - wr * sin);
+            }
+        }
+        len <<= 1;
+    }
+    Ok(())
+}
+
+/// Find the strongest oscillations in `samples` taken at `sample_rate` Hz, filling `peaks` from
+/// the strongest down. Returns how many peaks were found.
+/// The samples are windowed and transformed in place, `scratch` has to be as long as `samples`.
+pub fn analyze(
+    samples: &mut [f32],
+    scratch: &mut [f32],
+    sample_rate: f32,
+    peaks: &mut [Peak],
+) -> Result<usize, Error> {
+    let n = check_length(samples, scratch)?;
+
+    // An offset isn't an oscillation, and the window keeps the ends of the capture from leaking
+    // into every frequency
+    let mean = samples.iter().sum::<f32>() / n as f32;
+    let (cos, sin) = unit_root(n);
+    let (mut wr, mut wi) = (1., 0.);
+    for (sample, imaginary) in samples.iter_mut().zip(scratch.iter_mut()) {
+        *sample = (*sample - mean) * 0.5 * (1. - wr);
+        *imaginary = 0.;
+        (wr, wi) = (wr * cos - wi * sin, wi * cos + wr * sin);
+    }
+    fft(samples, scratch)?;
+
+    // One sided amplitude, the window halves the signal
+    let bins = n / 2 + 1;
+    for bin in 0..bins {
+        samples[bin] =
+            4. / n as f32 * sqrt(samples[bin] * samples[bin] + scratch[bin] * scratch[bin]);
+    }
+    let amplitudes = &samples[..bins];
+    let bin_width = sample_rate / n as f32;
+
+    let mut found = 0;
+    for bin in 1..bins - 1 {
+        let (before, at, after) = (amplitudes[bin - 1], amplitudes[bin], amplitudes[bin + 1]);
+        if at <= before || at < after || at == 0. {
+            continue;
+        }
+        // The top of a parabola through the bin and its neighbours is closer to the real peak
+        let curve = before - 2. * at + after;
+        let offset = if curve == 0. {
+            0.
+        } else {
+            0.5 * (before - after) / curve
+        };
+        let peak = Peak {
+            frequency: (bin as f32 + offset) * bin_width,
+            amplitude: at - 0.25 * (before - after) * offset,
+        };
+
+        let Some(rank) = peaks[..found]
+            .iter()
+            .position(|other| peak.amplitude > other.amplitude)
+            .or((found < peaks.len()).then_some(found))
+        else {
+            continue;
+        };
+        found = (found + 1).min(peaks.len());
+        peaks.copy_within(rank..found - 1, rank + 1);
+        peaks[rank] = peak;
+    }
+    Ok(found)
+}
+
+fn check_length(first: &[f32], second: &[f32]) -> Result<usize, Error> {
+    let n = first.len();
+    if n != second.len() || !(4..=MAX_SAMPLES).contains(&n) || !n.is_power_of_two() {
+        return Err(Error::Length(n));
+    }
+    Ok(n)
+}
+
+// Cosine and sine of 2π/`n` for a power of two, halving the angle from π down
+fn unit_root(n: usize) -> (f32, f32) {
+    match n {
+        0..=2 => (-1., 0.),
+        4 => (0., 1.),
+        _ => {
+            let (cos, sin) = unit_root(n / 2);
+            let half_cos = sqrt((1. + cos) / 2.);
+            (half_cos, sin / (2. * half_cos))
+        }
+    }
+}
+
+// Newton's method from an estimate halving the exponent, exact to the last bit or two
+fn sqrt(x: f32) -> f32 {
+    if x <= 0. {
+        return 0.;
+    }
+    let mut root = f32::from_bits((x.to_bits() >> 1) + 0x1fc0_0000);
+    for _ in 0..4 {
+        root = 0.5 * (root + x / root);
+    }
+    root
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use core::f32::consts::TAU;
+    use std::vec::Vec;
+
+    use super::{analyze, fft, sqrt, unit_root, Error, Peak};
+
+    fn sine(n: usize, sample_rate: f32, waves: &[(f32, f32)]) -> Vec<f32> {
+        (0..n)
+            .map(|i| {
+                let t = i as f32 / sample_rate;
+                waves
+                    .iter()
+                    .map(|(frequency, amplitude)| amplitude * (TAU * frequency * t).sin())
+                    .sum::<f32>()
+                    + 3.
+            })
+            .collect()
+    }
+
+    #[test]
+    fn helpers_are_accurate() {
+        for x in [1e-6, 0.25, 2., 12345.] {
+            assert!((sqrt(x) - x.sqrt()).abs() <= x.sqrt() * 1e-6);
+        }
+        for n in [8, 64, 1024] {
+            let (cos, sin) = unit_root(n);
+            let angle = TAU / n as f32;
+            assert!((cos - angle.cos()).abs() < 1e-6);
+            assert!((sin - angle.sin()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn matches_direct_transform() {
+        let n = 64;
+        let signal: Vec<f32> = (0..n).map(|i| ((i * 37 % 11) as f32 - 5.) / 3.).collect();
+        let mut re = signal.clone();
+        let mut im = std::vec![0.; n];
+        fft(&mut re, &mut im).unwrap();
+
+        for k in 0..n {
+            let (mut expected_re, mut expected_im) = (0., 0.);
+            for (i, sample) in signal.iter().enumerate() {
+                let angle = -TAU * (k * i) as f32 / n as f32;
+                expected_re += sample * angle.cos();
+                expected_im += sample * angle.sin();
+            }
+            assert!((re[k] - expected_re).abs() < 1e-3, "bin {k}");
+            assert!((im[k] - expected_im).abs() < 1e-3, "bin {k}");
+        }
+    }
+
+    #[test]
+    fn finds_strongest_peaks() {
+        let sample_rate = 1000.;
+        let mut samples = sine(1024, sample_rate, &[(42.3, 0.5), (87.9, 2.), (180., 1.)]);
+        let mut scratch = std::vec![0.; 1024];
+        let mut peaks = [Peak {
+            frequency: 0.,
+            amplitude: 0.,
+        }; 3];
+        assert_eq!(
+            analyze(&mut samples, &mut scratch, sample_rate, &mut peaks),
+            Ok(3)
+        );
+
+        // A bin is just under 1Hz wide
+        for (peak, (frequency, amplitude)) in
+            peaks.iter().zip([(87.9, 2.), (180., 1.), (42.3, 0.5)])
+        {
+            assert!((peak.frequency - frequency).abs() < 0.1, "{peak:?}");
+            assert!(
+                (peak.amplitude - amplitude).abs() < amplitude * 0.05,
+                "{peak:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_only_the_strongest() {
+        let sample_rate = 500.;
+        let mut samples = sine(256, sample_rate, &[(20., 1.), (60., 3.), (100., 2.)]);
+        let mut scratch = std::vec![0.; 256];
+        let mut peaks = [Peak {
+            frequency: 0.,
+            amplitude: 0.,
+        }; 1];
+        assert_eq!(
+            analyze(&mut samples, &mut scratch, sample_rate, &mut peaks),
+            Ok(1)
+        );
+        assert!((peaks[0].frequency - 60.).abs() < 0.5);
+    }
+
+    #[test]
+    fn rejects_invalid_length() {
+        let mut re = [0.; 100];
+        let mut im = [0.; 100];
+        assert_eq!(fft(&mut re, &mut im), Err(Error::Length(100)));
+        assert_eq!(fft(&mut re[..64], &mut im[..32]), Err(Error::Length(64)));
+        assert_eq!(fft(&mut re[..2], &mut im[..2]), Err(Error::Length(2)));
+    }
+}
@@ -0,0 +1,203 @@
+/// Stall detection errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// Value does not map to a [`StallAction`].
+    InvalidAction(u8),
+}
+
+/// Reaction to a detected stall.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum StallAction {
+    /// Do nothing.
+    Ignore = 0,
+    /// Warn the host.
+    Warn = 1,
+    /// Trigger the active trsync, like an endstop would.
+    Trigger = 2,
+    /// Shut the MCU down.
+    Shutdown = 3,
+}
+
+impl TryFrom<u8> for StallAction {
+    type Error = Error;
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            0 => Ok(Self::Ignore),
+            1 => Ok(Self::Warn),
+            2 => Ok(Self::Trigger),
+            3 => Ok(Self::Shutdown),
+            _ => Err(Error::InvalidAction(byte)),
+        }
+    }
+}
+
+impl From<StallAction> for u8 {
+    fn from(action: StallAction) -> Self {
+        action as Self
+    }
+}
+
+/// Stall detection settings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StallConfig {
+    /// Following error, in steps, above which the motor is considered to be stalling.
+    pub threshold: u32,
+    /// Consecutive samples above the threshold before a stall is reported.
+    pub samples: u8,
+    /// Reaction to a stall.
+    pub action: StallAction,
+}
+
+impl Default for StallConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 32,
+            samples: 2,
+            action: StallAction::Ignore,
+        }
+    }
+}
+
+/// Detects skipped steps from the encoder following error while the motor is moving.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StallDetector {
+    config: StallConfig,
+    over_threshold: u8,
+    stalled: bool,
+}
+
+impl StallDetector {
+    /// Create a new detector with the given settings.
+    pub const fn new(config: StallConfig) -> Self {
+        Self {
+            config,
+            over_threshold: 0,
+            stalled: false,
+        }
+    }
+
+    /// Get the current settings.
+    pub fn config(&self) -> StallConfig {
+        self.config
+    }
+
+    /// Replace the current settings, this re-arms the detector if they changed.
+    pub fn set_config(&mut self, config: StallConfig) {
+        if self.config != config {
+            *self = Self::new(config);
+        }
+    }
+
+    /// Whether a stall has been reported and the error has not recovered since.
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Re-arm the detector.
+    pub fn reset(&mut self) {
+        self.over_threshold = 0;
+        self.stalled = false;
+    }
+
+    /// Feed a following error sample, in steps.
+    /// Returns the configured action once when a stall is detected, the detector re-arms as soon as
+    /// the following error is back within the threshold.
+    pub fn update(&mut self, following_error: i32, moving: bool) -> Option<StallAction> {
+        if following_error.unsigned_abs() <= self.config.threshold {
+            self.reset();
+            return None;
+        }
+
+        // A motor pushed around at rest has not skipped any commanded steps
+        if !moving || self.stalled {
+            return None;
+        }
+
+        self.over_threshold = self.over_threshold.saturating_add(1);
+        if self.over_threshold < self.config.samples.max(1) {
+            return None;
+        }
+
+        self.stalled = true;
+        match self.config.action {
+            StallAction::Ignore => None,
+            action => Some(action),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StallAction, StallConfig, StallDetector};
+
+    fn detector(action: StallAction) -> StallDetector {
+        StallDetector::new(StallConfig {
+            threshold: 10,
+            samples: 3,
+            action,
+        })
+    }
+
+    #[test]
+    fn action_to_byte_roundtrip() {
+        for byte in 0..=u8::MAX {
+            if let Ok(action) = StallAction::try_from(byte) {
+                assert_eq!(u8::from(action), byte);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_after_consecutive_samples() {
+        let mut detector = detector(StallAction::Shutdown);
+        assert_eq!(detector.update(20, true), None);
+        assert_eq!(detector.update(-20, true), None);
+        assert_eq!(detector.update(20, true), Some(StallAction::Shutdown));
+        assert!(detector.is_stalled());
+        // Only reported once
+        assert_eq!(detector.update(20, true), None);
+    }
+
+    #[test]
+    fn rearms_once_recovered() {
+        let mut detector = detector(StallAction::Warn);
+        for _ in 0..3 {
+            detector.update(50, true);
+        }
+        assert!(detector.is_stalled());
+        assert_eq!(detector.update(5, true), None);
+        assert!(!detector.is_stalled());
+        assert_eq!(detector.update(50, true), None);
+        assert_eq!(detector.update(50, true), None);
+        assert_eq!(detector.update(50, true), Some(StallAction::Warn));
+    }
+
+    #[test]
+    fn glitches_do_not_trigger() {
+        let mut detector = detector(StallAction::Trigger);
+        for _ in 0..10 {
+            assert_eq!(detector.update(50, true), None);
+            assert_eq!(detector.update(50, true), None);
+            assert_eq!(detector.update(0, true), None);
+        }
+    }
+
+    #[test]
+    fn ignores_error_at_rest() {
+        let mut detector = detector(StallAction::Trigger);
+        for _ in 0..10 {
+            assert_eq!(detector.update(50, false), None);
+        }
+        assert!(!detector.is_stalled());
+    }
+
+    #[test]
+    fn ignore_action_still_latches() {
+        let mut detector = detector(StallAction::Ignore);
+        for _ in 0..3 {
+            assert_eq!(detector.update(50, true), None);
+        }
+        assert!(detector.is_stalled());
+    }
+}
@@ -0,0 +1,192 @@
+/// Number of histogram buckets.
+pub const BUCKETS: usize = 8;
+
+/// Histogram bucket of a following error. Bucket 0 holds errors of 0 steps, bucket `n` errors of
+/// `2^(n-1)` to `2^n - 1` steps and the last bucket everything beyond.
+pub fn bucket(following_error: i32) -> usize {
+    let bits = u32::BITS - following_error.unsigned_abs().leading_zeros();
+    (bits as usize).min(BUCKETS - 1)
+}
+
+/// Following error statistics over a number of samples.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ErrorStats {
+    samples: u32,
+    min: i32,
+    max: i32,
+    sum_squares: u64,
+    histogram: [u32; BUCKETS],
+}
+
+impl ErrorStats {
+    /// Statistics without any sample.
+    pub const EMPTY: Self = Self {
+        samples: 0,
+        min: 0,
+        max: 0,
+        sum_squares: 0,
+        histogram: [0; BUCKETS],
+    };
+
+    /// Add a following error sample, in steps.
+    pub fn record(&mut self, following_error: i32) {
+        if self.samples == 0 {
+            self.min = following_error;
+            self.max = following_error;
+        } else {
+            self.min = self.min.min(following_error);
+            self.max = self.max.max(following_error);
+        }
+        self.samples = self.samples.saturating_add(1);
+        let square = following_error.unsigned_abs() as u64 * following_error.unsigned_abs() as u64;
+        self.sum_squares = self.sum_squares.saturating_add(square);
+        let count = &mut self.histogram[bucket(following_error)];
+        *count = count.saturating_add(1);
+    }
+
+    /// Number of samples recorded.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Smallest following error, 0 without samples.
+    pub fn min(&self) -> i32 {
+        self.min
+    }
+
+    /// Largest following error, 0 without samples.
+    pub fn max(&self) -> i32 {
+        self.max
+    }
+
+    /// Root mean square of the following error in thousandths of a step, 0 without samples.
+    pub fn rms_milli(&self) -> u32 {
+        if self.samples == 0 {
+            return 0;
+        }
+        let mean_square = self.sum_squares as u128 * 1_000_000 / self.samples as u128;
+        mean_square.isqrt().min(u32::MAX as u128) as u32
+    }
+
+    /// Sample count of every bucket, see [`bucket`].
+    pub fn histogram(&self) -> [u32; BUCKETS] {
+        self.histogram
+    }
+}
+
+impl Default for ErrorStats {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+/// Accumulates following error statistics over consecutive windows of samples.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ErrorWindow {
+    window: u32,
+    current: ErrorStats,
+    completed: Option<ErrorStats>,
+}
+
+impl ErrorWindow {
+    /// Create a new accumulator over windows of `window` samples, 0 never completes a window.
+    pub const fn new(window: u32) -> Self {
+        Self {
+            window,
+            current: ErrorStats::EMPTY,
+            completed: None,
+        }
+    }
+
+    /// Samples per window.
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// Change the samples per window, starting over.
+    pub fn set_window(&mut self, window: u32) {
+        *self = Self::new(window);
+    }
+
+    /// Drop every sample, keeping the window size.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.window);
+    }
+
+    /// Add a following error sample, in steps.
+    pub fn record(&mut self, following_error: i32) {
+        self.current.record(following_error);
+        if self.window != 0 && self.current.samples() >= self.window {
+            self.completed = Some(core::mem::take(&mut self.current));
+        }
+    }
+
+    /// Window being accumulated.
+    pub fn current(&self) -> ErrorStats {
+        self.current
+    }
+
+    /// Last complete window, if any.
+    pub fn completed(&self) -> Option<ErrorStats> {
+        self.completed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bucket, ErrorStats, ErrorWindow, BUCKETS};
+
+    #[test]
+    fn buckets_by_power_of_two() {
+        assert_eq!(bucket(0), 0);
+        assert_eq!(bucket(1), 1);
+        assert_eq!(bucket(-3), 2);
+        assert_eq!(bucket(4), 3);
+        assert_eq!(bucket(63), 6);
+        assert_eq!(bucket(64), BUCKETS - 1);
+        assert_eq!(bucket(i32::MIN), BUCKETS - 1);
+    }
+
+    #[test]
+    fn accumulates_samples() {
+        let mut stats = ErrorStats::default();
+        assert_eq!(stats.rms_milli(), 0);
+        for error in [3, -4, 0, 5] {
+            stats.record(error);
+        }
+        assert_eq!(stats.samples(), 4);
+        assert_eq!(stats.min(), -4);
+        assert_eq!(stats.max(), 5);
+        // sqrt((9 + 16 + 0 + 25) / 4)
+        assert_eq!(stats.rms_milli(), 3535);
+        assert_eq!(stats.histogram(), [1, 0, 1, 2, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn min_and_max_start_from_first_sample() {
+        let mut stats = ErrorStats::default();
+        stats.record(7);
+        assert_eq!((stats.min(), stats.max()), (7, 7));
+    }
+
+    #[test]
+    fn completes_windows() {
+        let mut window = ErrorWindow::new(3);
+        window.record(1);
+        window.record(2);
+        assert_eq!(window.completed(), None);
+        window.record(3);
+        let completed = window.completed().unwrap();
+        assert_eq!(completed.samples(), 3);
+        assert_eq!(completed.max(), 3);
+        assert_eq!(window.current().samples(), 0);
+
+        window.record(10);
+        assert_eq!(window.completed(), Some(completed));
+        assert_eq!(window.current().max(), 10);
+
+        window.reset();
+        assert_eq!(window.completed(), None);
+        assert_eq!(window.window(), 3);
+    }
+}
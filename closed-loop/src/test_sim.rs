@@ -0,0 +1,274 @@
+use crate::{
+    autotune::StepResponse,
+    correction::{CorrectionConfig, Corrector},
+    geometry::EncoderGeometry,
+    hold::{HoldAction, HoldConfig, HoldEvent, IdleHold},
+    homing::RefinementConfig,
+    sim::{Plant, PlantConfig},
+    stall::{StallAction, StallConfig, StallDetector},
+};
+
+// Time between encoder readings, in seconds
+const READ_INTERVAL: f32 = 0.001;
+
+// Time between steps, in seconds
+const STEP_INTERVAL: f32 = 0.0002;
+
+// Stands in for the encoder task, the steps the host commanded are counted apart from corrections
+struct Rig {
+    plant: Plant,
+    geometry: EncoderGeometry,
+    commanded: i32,
+    last_angle: u16,
+    ticks: i64,
+}
+
+impl Rig {
+    fn new(config: PlantConfig) -> Self {
+        let mut plant = Plant::new(config, 7);
+        let last_angle = plant.angle();
+        Self {
+            plant,
+            geometry: config.geometry,
+            commanded: 0,
+            last_angle,
+            ticks: 0,
+        }
+    }
+
+    fn command(&mut self, steps: i32) {
+        for _ in 0..steps.unsigned_abs() {
+            self.plant.step(steps > 0);
+            self.commanded += steps.signum();
+            self.plant.advance(STEP_INTERVAL);
+        }
+    }
+
+    fn correct(&mut self, steps: i32) {
+        for _ in 0..steps.unsigned_abs() {
+            self.plant.step(steps > 0);
+            self.plant.advance(STEP_INTERVAL);
+        }
+    }
+
+    // Reads the encoder and returns the following error, in steps
+    fn following_error(&mut self) -> i32 {
+        let resolution = self.geometry.resolution() as i32;
+        let angle = self.plant.angle();
+        let mut delta = angle as i32 - self.last_angle as i32;
+        if delta > resolution / 2 {
+            delta -= resolution;
+        } else if delta < -resolution / 2 {
+            delta += resolution;
+        }
+        self.last_angle = angle;
+        self.ticks += delta as i64;
+        (self.geometry.ticks_to_steps(self.ticks) as i32).wrapping_sub(self.commanded)
+    }
+}
+
+#[test]
+fn follows_commanded_moves() {
+    for inverted in [false, true] {
+        let mut rig = Rig::new(PlantConfig {
+            geometry: EncoderGeometry::new(200, 16, 4096, inverted).unwrap(),
+            ..PlantConfig::default()
+        });
+        // Read often enough to keep track of the turns
+        for _ in 0..300 {
+            rig.command(16);
+            rig.following_error();
+        }
+        for _ in 0..100 {
+            rig.command(-16);
+            rig.following_error();
+        }
+        rig.plant.advance(0.2);
+        assert!(rig.following_error().abs() <= 2);
+        assert_eq!(rig.plant.lost_steps(), 0);
+    }
+}
+
+#[test]
+fn corrects_slipped_steps() {
+    let mut rig = Rig::new(PlantConfig::default());
+    let mut corrector = Corrector::new(CorrectionConfig::default());
+    rig.command(800);
+
+    // Something jammed the axis for a moment
+    rig.plant.set_load(-0.6);
+    rig.plant.advance(0.003);
+    rig.plant.set_load(0.);
+    rig.plant.advance(0.2);
+    let slipped = rig.following_error();
+    assert!(slipped <= -64);
+
+    let mut following_error = slipped;
+    for _ in 0..50 {
+        let steps = corrector.update(following_error);
+        if steps == 0 {
+            break;
+        }
+        rig.correct(steps);
+        rig.plant.advance(READ_INTERVAL);
+        following_error = rig.following_error();
+    }
+    assert!(following_error.unsigned_abs() <= corrector.config().band);
+}
+
+#[test]
+fn detects_stall_under_overload() {
+    let stall = StallConfig {
+        threshold: 32,
+        samples: 2,
+        action: StallAction::Trigger,
+    };
+
+    let mut rig = Rig::new(PlantConfig::default());
+    let mut detector = StallDetector::new(stall);
+    for _ in 0..100 {
+        rig.command(5);
+        assert_eq!(detector.update(rig.following_error(), true), None);
+    }
+
+    rig.plant.set_load(-0.5);
+    let mut detected = None;
+    for _ in 0..100 {
+        rig.command(5);
+        detected = detector.update(rig.following_error(), true);
+        if detected.is_some() {
+            break;
+        }
+    }
+    assert_eq!(detected, Some(StallAction::Trigger));
+}
+
+#[test]
+fn idle_hold_restores_pushed_motor() {
+    let mut rig = Rig::new(PlantConfig::default());
+    let mut hold = IdleHold::new(HoldConfig {
+        threshold: 4,
+        action: HoldAction::Restore,
+        max_steps: 16,
+    });
+    rig.command(320);
+    rig.plant.advance(0.1);
+    assert_eq!(hold.update(rig.following_error(), true), None);
+
+    // Pushed far enough to fall into another electrical period
+    rig.plant.displace(100.);
+    rig.plant.advance(0.2);
+    let pushed = rig.following_error();
+    assert!(pushed >= 64);
+    assert_eq!(
+        hold.update(pushed, true),
+        Some(HoldEvent::Disturbed(pushed))
+    );
+
+    let mut following_error = pushed;
+    let mut event = None;
+    for _ in 0..50 {
+        rig.correct(hold.restore_steps(following_error));
+        rig.plant.advance(READ_INTERVAL);
+        following_error = rig.following_error();
+        event = hold.update(following_error, true);
+        if event.is_some() {
+            break;
+        }
+    }
+    assert_eq!(event, Some(HoldEvent::Settled));
+    assert_eq!(rig.commanded, 320);
+}
+
+#[test]
+fn autotune_from_plant_response() {
+    let mut rig = Rig::new(PlantConfig::default());
+    let mut response = StepResponse::new(400);
+    for _ in 0..32 {
+        rig.plant.advance(READ_INTERVAL);
+        response.rest(rig.following_error());
+    }
+    for _ in 0..80 {
+        rig.command(5);
+        response.moving(rig.following_error());
+    }
+    // Elapsed time in microseconds
+    for elapsed in 1..=100 {
+        rig.plant.advance(READ_INTERVAL);
+        response.settling(rig.following_error(), elapsed * 1000);
+    }
+
+    let tuning = response.tune(200).unwrap();
+    assert!(tuning.correction.band >= 1);
+    assert!(tuning.correction.gain > 0. && tuning.correction.gain <= 1.);
+    assert!(tuning.stall_threshold > tuning.correction.band);
+
+    // The tuned corrector brings back steps lost to a jam
+    let mut corrector = Corrector::new(tuning.correction);
+    rig.plant.set_load(-0.6);
+    rig.plant.advance(0.003);
+    rig.plant.set_load(0.);
+    rig.plant.advance(0.2);
+    let mut following_error = rig.following_error();
+    assert!(following_error.unsigned_abs() > tuning.correction.band);
+    for _ in 0..100 {
+        let steps = corrector.update(following_error);
+        if steps == 0 {
+            break;
+        }
+        rig.correct(steps);
+        rig.plant.advance(READ_INTERVAL);
+        following_error = rig.following_error();
+    }
+    assert!(following_error.unsigned_abs() <= tuning.correction.band);
+}
+
+#[test]
+fn discovers_geometry_from_travel() {
+    for geometry in [
+        EncoderGeometry::new(200, 16, 4096, true).unwrap(),
+        EncoderGeometry::new(400, 32, 4096, false).unwrap(),
+    ] {
+        let mut rig = Rig::new(PlantConfig {
+            geometry,
+            ..PlantConfig::default()
+        });
+        // Measured as an unknown motor, the rig keeps counting ticks the plain way
+        rig.geometry = EncoderGeometry::DEFAULT;
+        for _ in 0..200 {
+            rig.command(16);
+            rig.following_error();
+        }
+        rig.plant.advance(0.2);
+        rig.following_error();
+        assert_eq!(
+            EncoderGeometry::from_travel(3200, rig.ticks, geometry.microsteps(), 4096),
+            Ok(geometry)
+        );
+    }
+}
+
+#[test]
+fn refined_homing_ends_at_the_same_angle() {
+    let refinement = RefinementConfig {
+        angle: 100,
+        period: 4,
+        max_steps: 64,
+    };
+    // The switch triggers a little differently every time
+    for trigger in [0, 3, 11, 27, 40] {
+        let mut rig = Rig::new(PlantConfig {
+            noise: 0,
+            ..PlantConfig::default()
+        });
+        rig.command(762 + trigger);
+        rig.plant.advance(0.1);
+
+        let steps = refinement.steps(&rig.geometry, rig.plant.angle()).unwrap();
+        rig.correct(steps);
+        rig.plant.advance(0.1);
+        // 4 full steps are 81.92 ticks, every trigger is closest to the target at 1001.12
+        let angle = rig.plant.angle() as i32;
+        assert!((angle - 1001).abs() <= 1, "ended at {angle}");
+    }
+}
@@ -0,0 +1,138 @@
+/// Angle window errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// Encoder resolution of 0.
+    Resolution,
+    /// Bound at or beyond the encoder resolution.
+    Bound(u16),
+}
+
+/// Range of encoder angles going up from `start` to `end`, through 0 when `end` is below `start`.
+/// A window where `start` and `end` are the same angle is a threshold.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AngleWindow {
+    start: u16,
+    end: u16,
+    resolution: u16,
+}
+
+impl AngleWindow {
+    /// Create a new window, the bounds are in ticks of an encoder of `resolution` ticks per
+    /// revolution and are part of the window.
+    pub fn new(start: u16, end: u16, resolution: u16) -> Result<Self, Error> {
+        if resolution == 0 {
+            return Err(Error::Resolution);
+        }
+        for bound in [start, end] {
+            if bound >= resolution {
+                return Err(Error::Bound(bound));
+            }
+        }
+
+        Ok(Self {
+            start,
+            end,
+            resolution,
+        })
+    }
+
+    /// First angle of the window.
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// Last angle of the window.
+    pub fn end(&self) -> u16 {
+        self.end
+    }
+
+    /// Whether `angle` is within the window.
+    pub fn contains(&self, angle: u16) -> bool {
+        self.forward(self.start, angle) <= self.forward(self.start, self.end)
+    }
+
+    /// Whether the shortest way from the `last` angle to `angle` went into the window.
+    /// Readings on either side of a threshold or a narrow window count, it is not missed at speed.
+    pub fn entered(&self, last: u16, angle: u16) -> bool {
+        self.contains(angle)
+            || self.on_way(last, angle, self.start)
+            || self.on_way(last, angle, self.end)
+    }
+
+    /// Whether reading `angle` after `last` reaches the side of the window given by `inside`.
+    pub fn reached(&self, last: u16, angle: u16, inside: bool) -> bool {
+        if inside {
+            self.entered(last, angle)
+        } else {
+            !self.contains(angle)
+        }
+    }
+
+    // Ticks going up from `from` to `to`
+    fn forward(&self, from: u16, to: u16) -> i32 {
+        (to as i32 - from as i32).rem_euclid(self.resolution as i32)
+    }
+
+    // Whether `point` is on the shortest way from `from` to `to`
+    fn on_way(&self, from: u16, to: u16, point: u16) -> bool {
+        let up = self.forward(from, to);
+        if up <= self.resolution as i32 / 2 {
+            self.forward(from, point) <= up
+        } else {
+            self.forward(point, from) <= self.resolution as i32 - up
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AngleWindow, Error};
+
+    #[test]
+    fn contains_angles() {
+        let window = AngleWindow::new(1000, 1100, 4096).unwrap();
+        assert!(window.contains(1000));
+        assert!(window.contains(1050));
+        assert!(window.contains(1100));
+        assert!(!window.contains(999));
+        assert!(!window.contains(1101));
+
+        // Through the zero of the encoder
+        let window = AngleWindow::new(4000, 100, 4096).unwrap();
+        assert!(window.contains(4050));
+        assert!(window.contains(0));
+        assert!(window.contains(100));
+        assert!(!window.contains(2000));
+    }
+
+    #[test]
+    fn crossing_a_threshold_enters() {
+        let threshold = AngleWindow::new(2048, 2048, 4096).unwrap();
+        assert!(threshold.entered(2000, 2100));
+        assert!(threshold.entered(2100, 2000));
+        assert!(!threshold.entered(2000, 2040));
+        // The short way around doesn't go through the threshold
+        assert!(!threshold.entered(4000, 100));
+
+        let threshold = AngleWindow::new(0, 0, 4096).unwrap();
+        assert!(threshold.entered(4000, 100));
+        assert!(threshold.entered(100, 4000));
+    }
+
+    #[test]
+    fn reaches_either_side() {
+        let window = AngleWindow::new(1000, 1100, 4096).unwrap();
+        // Went through the window between two readings
+        assert!(window.reached(900, 1200, true));
+        assert!(!window.reached(900, 950, true));
+        assert!(window.reached(1050, 1200, false));
+        assert!(!window.reached(1050, 1060, false));
+    }
+
+    #[test]
+    fn rejects_invalid_window() {
+        assert_eq!(AngleWindow::new(0, 0, 0), Err(Error::Resolution));
+        assert_eq!(AngleWindow::new(4096, 0, 4096), Err(Error::Bound(4096)));
+        assert_eq!(AngleWindow::new(0, 5000, 4096), Err(Error::Bound(5000)));
+    }
+}
@@ -0,0 +1,19 @@
+use core::time::Duration;
+
+/// Default i2c address of MT6701.
+pub const DEFAULT_I2C_ADDRESS: u8 = 0x06;
+
+/// Angle ticks per revolution (14-bit).
+pub const RESOLUTION: u16 = 16384;
+
+/// Zero position steps per revolution (12-bit).
+pub const ZERO_RESOLUTION: u16 = 4096;
+
+/// Time to program the EEPROM, the supply has to stay up in the meantime.
+pub const EEPROM_PROGRAMMING_TIME: Duration = Duration::from_millis(600);
+
+/// Key written to `EEPROM_KEY` to unlock EEPROM programming.
+pub const EEPROM_KEY: u8 = 0xB3;
+
+/// Command written to `EEPROM_PROGRAM` to start EEPROM programming.
+pub const EEPROM_PROGRAM: u8 = 0x05;
@@ -0,0 +1,26 @@
+/// Crate errors.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error<E> {
+    /// `I2C` or `SPI` communication error.
+    Communication(E),
+
+    /// SSI frame CRC does not match its content.
+    Crc {
+        /// CRC sent by the sensor.
+        received: u8,
+        /// CRC of the frame content.
+        computed: u8,
+    },
+
+    /// Magnetic field status bits not valid.
+    Status(u8),
+
+    /// Zero position beyond the 12-bit range.
+    ZeroPosition(u16),
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Self {
+        Self::Communication(e)
+    }
+}
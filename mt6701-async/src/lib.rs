@@ -0,0 +1,137 @@
+#![cfg_attr(not(test), no_std)]
+
+use constants::{
+    DEFAULT_I2C_ADDRESS, EEPROM_KEY, EEPROM_PROGRAM, EEPROM_PROGRAMMING_TIME, ZERO_RESOLUTION,
+};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+use error::Error;
+use register::Register;
+
+/// Constants.
+pub mod constants;
+/// Errors.
+pub mod error;
+/// Registers.
+pub(crate) mod register;
+/// SSI read-out.
+pub mod ssi;
+#[cfg(test)]
+mod test_reading;
+#[cfg(test)]
+mod test_writing;
+
+const DIRECTION_BIT: u8 = 0b10;
+
+/// Rotation direction counted as positive.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Direction {
+    /// Angle increases counter-clockwise, looking at the top of the chip.
+    CounterClockwise,
+    /// Angle increases clockwise, looking at the top of the chip.
+    Clockwise,
+}
+
+pub struct Mt6701<I2C> {
+    address: u8,
+    bus: I2C,
+}
+
+impl<E, I2C: I2c<Error = E>> Mt6701<I2C> {
+    /// Create a new Mt6701 driver instance.
+    pub fn new(bus: I2C) -> Self {
+        Self::with_address(DEFAULT_I2C_ADDRESS, bus)
+    }
+
+    /// Create a new Mt6701 driver instance.
+    pub fn with_address(address: u8, bus: I2C) -> Self {
+        Self { address, bus }
+    }
+
+    /// Release the bus, consuming the driver.
+    pub fn release(self) -> I2C {
+        self.bus
+    }
+
+    /// Get the angle.
+    pub async fn angle(&mut self) -> Result<u16, Error<E>> {
+        // 14-bit value, the low 6 bits are left aligned in the second register.
+        let mut buffer = [0u8; 2];
+        self.bus
+            .write_read(self.address, &[Register::AngleHigh.into()], &mut buffer)
+            .await?;
+        Ok((buffer[0] as u16) << 6 | (buffer[1] as u16) >> 2)
+    }
+
+    /// Get the rotation direction.
+    pub async fn direction(&mut self) -> Result<Direction, Error<E>> {
+        let bits = self.read_u8(Register::Direction).await?;
+        Ok(if bits & DIRECTION_BIT != 0 {
+            Direction::Clockwise
+        } else {
+            Direction::CounterClockwise
+        })
+    }
+
+    /// Set the rotation direction, until the next power cycle unless programmed to the EEPROM.
+    pub async fn set_direction(&mut self, direction: Direction) -> Result<(), Error<E>> {
+        let bits = self.read_u8(Register::Direction).await? & !DIRECTION_BIT;
+        let bits = match direction {
+            Direction::CounterClockwise => bits,
+            Direction::Clockwise => bits | DIRECTION_BIT,
+        };
+        self.write_u8(Register::Direction, bits).await
+    }
+
+    /// Get the zero position, in 1/4096 of a revolution.
+    pub async fn zero_position(&mut self) -> Result<u16, Error<E>> {
+        let high = self.read_u8(Register::ZeroHigh).await?;
+        let low = self.read_u8(Register::ZeroLow).await?;
+        Ok(((high & 0x0F) as u16) << 8 | low as u16)
+    }
+
+    /// Set the zero position, in 1/4096 of a revolution.
+    /// Lasts until the next power cycle unless programmed to the EEPROM.
+    pub async fn set_zero_position(&mut self, position: u16) -> Result<(), Error<E>> {
+        if position >= ZERO_RESOLUTION {
+            return Err(Error::ZeroPosition(position));
+        }
+        // The upper bits hold output settings
+        let high = self.read_u8(Register::ZeroHigh).await? & 0xF0;
+        self.write_u8(Register::ZeroHigh, high | (position >> 8) as u8)
+            .await?;
+        self.write_u8(Register::ZeroLow, position as u8).await
+    }
+
+    /// Program the current settings to the EEPROM, so they survive a power cycle.
+    /// The supply has to stay up until this returns.
+    pub async fn program_eeprom<D>(&mut self, delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayNs,
+    {
+        self.write_u8(Register::EepromKey, EEPROM_KEY).await?;
+        self.write_u8(Register::EepromProgram, EEPROM_PROGRAM)
+            .await?;
+        delay
+            .delay_ms(EEPROM_PROGRAMMING_TIME.as_millis() as u32)
+            .await;
+        Ok(())
+    }
+
+    /// Helper function for write-reading 1 byte from the given register.
+    async fn read_u8(&mut self, register: Register) -> Result<u8, Error<E>> {
+        let mut buffer = [0u8; 1];
+        self.bus
+            .write_read(self.address, &[register.into()], &mut buffer)
+            .await?;
+        Ok(buffer[0])
+    }
+
+    /// Helper function for writing 1 byte to the given register.
+    async fn write_u8(&mut self, register: Register, byte: u8) -> Result<(), Error<E>> {
+        Ok(self
+            .bus
+            .write(self.address, &[register.into(), byte])
+            .await?)
+    }
+}
@@ -0,0 +1,22 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Register {
+    /// Angle bits 13:6, reading it latches the low bits.
+    AngleHigh = 0x03,
+    /// EEPROM programming key.
+    EepromKey = 0x09,
+    /// EEPROM programming command.
+    EepromProgram = 0x0A,
+    /// Rotation direction in bit 1, output settings in the other bits.
+    Direction = 0x29,
+    /// Zero position bits 11:8 in bits 3:0, output settings in the other bits.
+    ZeroHigh = 0x32,
+    /// Zero position bits 7:0.
+    ZeroLow = 0x33,
+}
+
+impl From<Register> for u8 {
+    fn from(reg: Register) -> Self {
+        reg as Self
+    }
+}
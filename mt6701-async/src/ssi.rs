@@ -0,0 +1,116 @@
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::error::Error;
+
+// x^6 + x + 1
+const CRC_POLYNOMIAL: u8 = 0x03;
+
+/// Magnetic field strength.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u8)]
+pub enum Field {
+    /// Field within range.
+    Normal = 0b00,
+    /// Field too strong, magnet too close.
+    TooStrong = 0b01,
+    /// Field too weak, magnet too far.
+    TooWeak = 0b10,
+}
+
+/// Status bits sent along with the SSI angle.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Status {
+    /// Magnetic field strength.
+    pub field: Field,
+    /// Push button detected, the magnet moved towards the sensor.
+    pub button: bool,
+    /// Track loss, the magnet turned faster than the sensor can follow.
+    pub track_loss: bool,
+}
+
+impl TryFrom<u8> for Status {
+    type Error = u8;
+
+    /// Parse the 4 status bits, returns them back if the field bits are not valid.
+    fn try_from(bits: u8) -> Result<Self, Self::Error> {
+        let field = match bits & 0b11 {
+            0b00 => Field::Normal,
+            0b01 => Field::TooStrong,
+            0b10 => Field::TooWeak,
+            _ => return Err(bits),
+        };
+        Ok(Self {
+            field,
+            button: bits & 0b0100 != 0,
+            track_loss: bits & 0b1000 != 0,
+        })
+    }
+}
+
+/// Angle and status read over SSI.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Sample {
+    /// 14-bit angle.
+    pub angle: u16,
+    /// Field and motion status.
+    pub status: Status,
+}
+
+/// CRC6 of the 18 angle and status bits of a frame.
+pub fn crc6(data: u32) -> u8 {
+    let mut crc = 0u8;
+    for bit in (0..18).rev() {
+        let feedback = ((crc >> 5) ^ (data >> bit) as u8) & 1;
+        crc = (crc << 1) & 0x3F;
+        if feedback != 0 {
+            crc ^= CRC_POLYNOMIAL;
+        }
+    }
+    crc
+}
+
+/// Parse a 24 bit SSI frame: 14 angle bits, 4 status bits and 6 CRC bits.
+pub fn parse_frame<E>(frame: [u8; 3]) -> Result<Sample, Error<E>> {
+    let frame = u32::from_be_bytes([0, frame[0], frame[1], frame[2]]);
+    let data = frame >> 6;
+    let received = (frame & 0x3F) as u8;
+    let computed = crc6(data);
+    if received != computed {
+        return Err(Error::Crc { received, computed });
+    }
+
+    let status = Status::try_from((data & 0x0F) as u8).map_err(Error::Status)?;
+    Ok(Sample {
+        angle: (data >> 4) as u16,
+        status,
+    })
+}
+
+/// MT6701 read out over its SSI interface, on an SPI bus without MOSI.
+pub struct Mt6701Ssi<SPI> {
+    spi: SPI,
+}
+
+impl<E, SPI: SpiDevice<Error = E>> Mt6701Ssi<SPI> {
+    /// Create a new MT6701 SSI driver instance.
+    pub fn new(spi: SPI) -> Self {
+        Self { spi }
+    }
+
+    /// Release the bus, consuming the driver.
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Get the angle along with the field status.
+    pub async fn sample(&mut self) -> Result<Sample, Error<E>> {
+        let mut frame = [0u8; 3];
+        self.spi.read(&mut frame).await?;
+        parse_frame(frame)
+    }
+
+    /// Get the angle.
+    pub async fn angle(&mut self) -> Result<u16, Error<E>> {
+        Ok(self.sample().await?.angle)
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{
+    error::Error,
+    ssi::{crc6, Field, Mt6701Ssi, Sample, Status},
+    Direction, Mt6701,
+};
+use embassy_futures::block_on;
+use embedded_hal_mock::eh1::{
+    i2c::{Mock as I2cMock, Transaction as I2cTransaction},
+    spi::{Mock as SpiMock, Transaction as SpiTransaction},
+};
+
+fn ssi_frame(frame: [u8; 3]) -> SpiMock<u8> {
+    SpiMock::new(&[
+        SpiTransaction::transaction_start(),
+        SpiTransaction::read_vec(frame.to_vec()),
+        SpiTransaction::transaction_end(),
+    ])
+}
+
+#[test]
+fn reads_angle() {
+    let i2c = I2cMock::new(&[
+        I2cTransaction::write_read(0x06, vec![0x03], vec![0x48, 0xD0]),
+        I2cTransaction::write_read(0x06, vec![0x03], vec![0xFF, 0xFC]),
+        I2cTransaction::write_read(0x06, vec![0x03], vec![0x00, 0x03]),
+    ]);
+    let mut mt6701 = Mt6701::new(i2c);
+    assert_eq!(block_on(mt6701.angle()), Ok(0x1234));
+    assert_eq!(block_on(mt6701.angle()), Ok(0x3FFF));
+    // Bits below the angle are ignored
+    assert_eq!(block_on(mt6701.angle()), Ok(0));
+    mt6701.release().done();
+}
+
+#[test]
+fn reads_direction() {
+    let i2c = I2cMock::new(&[
+        I2cTransaction::write_read(0x06, vec![0x29], vec![0b0100_0010]),
+        I2cTransaction::write_read(0x06, vec![0x29], vec![0b0100_0000]),
+    ]);
+    let mut mt6701 = Mt6701::new(i2c);
+    assert_eq!(block_on(mt6701.direction()), Ok(Direction::Clockwise));
+    assert_eq!(
+        block_on(mt6701.direction()),
+        Ok(Direction::CounterClockwise)
+    );
+    mt6701.release().done();
+}
+
+#[test]
+fn reads_zero_position() {
+    let i2c = I2cMock::new(&[
+        I2cTransaction::write_read(0x06, vec![0x32], vec![0xA5]),
+        I2cTransaction::write_read(0x06, vec![0x33], vec![0x3C]),
+    ]);
+    let mut mt6701 = Mt6701::new(i2c);
+    assert_eq!(block_on(mt6701.zero_position()), Ok(0x053C));
+    mt6701.release().done();
+}
+
+#[test]
+fn crc_matches_frames() {
+    assert_eq!(crc6(0x1234 << 4), 0x12);
+    assert_eq!(crc6(0x3FFF << 4 | 0b0110), 0x15);
+    assert_eq!(crc6(0), 0);
+}
+
+#[test]
+fn reads_ssi_sample() {
+    let mut mt6701 = Mt6701Ssi::new(ssi_frame([0xFF, 0xFD, 0x95]));
+    assert_eq!(
+        block_on(mt6701.sample()),
+        Ok(Sample {
+            angle: 0x3FFF,
+            status: Status {
+                field: Field::TooWeak,
+                button: true,
+                track_loss: false,
+            },
+        })
+    );
+    mt6701.release().done();
+
+    let mut mt6701 = Mt6701Ssi::new(ssi_frame([0x00, 0x02, 0x5B]));
+    assert_eq!(
+        block_on(mt6701.sample()),
+        Ok(Sample {
+            angle: 0,
+            status: Status {
+                field: Field::TooStrong,
+                button: false,
+                track_loss: true,
+            },
+        })
+    );
+    mt6701.release().done();
+}
+
+#[test]
+fn rejects_corrupted_ssi_frame() {
+    let mut mt6701 = Mt6701Ssi::new(ssi_frame([0x48, 0xD1, 0x12]));
+    assert!(matches!(
+        block_on(mt6701.angle()),
+        Err(Error::Crc { received: 0x12, .. })
+    ));
+    mt6701.release().done();
+}
+
+#[test]
+fn rejects_invalid_field_status() {
+    let mut mt6701 = Mt6701Ssi::new(ssi_frame([0x80, 0x00, 0xEC]));
+    assert_eq!(block_on(mt6701.angle()), Err(Error::Status(0b0011)));
+    mt6701.release().done();
+}
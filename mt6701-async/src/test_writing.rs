@@ -0,0 +1,60 @@
+use crate::{error::Error, Direction, Mt6701};
+use embassy_futures::block_on;
+use embedded_hal_mock::eh1::{
+    delay::NoopDelay,
+    i2c::{Mock, Transaction},
+};
+
+#[test]
+fn sets_direction_keeping_other_bits() {
+    let i2c = Mock::new(&[
+        Transaction::write_read(0x06, vec![0x29], vec![0b0100_0000]),
+        Transaction::write(0x06, vec![0x29, 0b0100_0010]),
+        Transaction::write_read(0x06, vec![0x29], vec![0b1111_1111]),
+        Transaction::write(0x06, vec![0x29, 0b1111_1101]),
+    ]);
+    let mut mt6701 = Mt6701::new(i2c);
+    assert_eq!(block_on(mt6701.set_direction(Direction::Clockwise)), Ok(()));
+    assert_eq!(
+        block_on(mt6701.set_direction(Direction::CounterClockwise)),
+        Ok(())
+    );
+    mt6701.release().done();
+}
+
+#[test]
+fn sets_zero_position_keeping_other_bits() {
+    let i2c = Mock::new(&[
+        Transaction::write_read(0x06, vec![0x32], vec![0xA0]),
+        Transaction::write(0x06, vec![0x32, 0xA5]),
+        Transaction::write(0x06, vec![0x33, 0x3C]),
+    ]);
+    let mut mt6701 = Mt6701::new(i2c);
+    assert_eq!(block_on(mt6701.set_zero_position(0x053C)), Ok(()));
+    mt6701.release().done();
+}
+
+#[test]
+fn rejects_zero_position_out_of_range() {
+    let i2c = Mock::new(&[]);
+    let mut mt6701 = Mt6701::new(i2c);
+    assert_eq!(
+        block_on(mt6701.set_zero_position(4096)),
+        Err(Error::ZeroPosition(4096))
+    );
+    mt6701.release().done();
+}
+
+#[test]
+fn programs_eeprom() {
+    let i2c = Mock::new(&[
+        Transaction::write(0x06, vec![0x09, 0xB3]),
+        Transaction::write(0x06, vec![0x0A, 0x05]),
+    ]);
+    let mut mt6701 = Mt6701::new(i2c);
+    assert_eq!(
+        block_on(mt6701.program_eeprom(&mut NoopDelay::new())),
+        Ok(())
+    );
+    mt6701.release().done();
+}
@@ -0,0 +1,57 @@
+use anchor::*;
+
+// Largest payload of a `sensor_bulk_data` response
+const BULK_DATA_SIZE: usize = 51;
+
+// Samples are a time code followed by the little endian 16 bit angle
+const BYTES_PER_SAMPLE: usize = 3;
+
+// Time code marking the sample as an error report instead of an angle
+pub const TCODE_ERROR: u8 = 0xff;
+
+/// Batches sensor samples into `sensor_bulk_data` responses.
+pub struct SensorBulk {
+    oid: u8,
+    sequence: u16,
+    data: [u8; BULK_DATA_SIZE],
+    data_count: usize,
+}
+
+impl SensorBulk {
+    pub fn new(oid: u8) -> Self {
+        Self {
+            oid,
+            sequence: 0,
+            data: [0; BULK_DATA_SIZE],
+            data_count: 0,
+        }
+    }
+
+    pub fn add(&mut self, tcode: u8, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.data[self.data_count] = tcode;
+        self.data[self.data_count + 1] = low;
+        self.data[self.data_count + 2] = high;
+        self.data_count += BYTES_PER_SAMPLE;
+
+        if self.data_count + BYTES_PER_SAMPLE > BULK_DATA_SIZE {
+            self.report();
+        }
+    }
+
+    pub fn add_error(&mut self, error: u8) {
+        self.add(TCODE_ERROR, error as u16);
+    }
+
+    // Sends whatever has been collected so far, even if it is an empty message
+    pub fn report(&mut self) {
+        klipper_reply!(
+            sensor_bulk_data,
+            oid: u8 = self.oid,
+            sequence: u16 = self.sequence,
+            data: &[u8] = &self.data[..self.data_count]
+        );
+        self.sequence = self.sequence.wrapping_add(1);
+        self.data_count = 0;
+    }
+}
@@ -0,0 +1,74 @@
+use anchor::*;
+use embassy_time::Instant;
+use heapless::Entry;
+
+use crate::klipper::encoder::{send_encoder_message, EncoderMessage};
+use crate::klipper::oid_types::*;
+
+mod bulk;
+mod query;
+mod spi;
+
+pub use query::AngleQuery;
+
+// The samples are sent in the AS5047D's bulk format, which the host's angle module already knows
+klipper_enumeration! {
+    #[derive(Debug)]
+    #[klipper_enumeration(name = "spi_angle_type", rename_all = "lowercase")]
+    enum SpiAngleType {
+        As5047d
+    }
+}
+
+/// Streams the AS5600 to an unmodified host as an `as5047d`, configured with `sensor_type: as5047d`
+/// and `cs_pin: None` in the `[angle]` section. The AS5600 sits on the board's I2C bus, so the
+/// `spi_oid` device is accepted for compatibility but not used
+#[klipper_command]
+pub fn config_spi_angle(context: &mut crate::State, oid: u8, spi_oid: u8, spi_angle_type: u8) {
+    log::trace!("[ANCHOR] Config SPI Angle - oid: {oid}, spi_oid: {spi_oid}, spi_angle_type: {spi_angle_type}");
+
+    if spi_angle_type != SpiAngleType::As5047d as u8 {
+        klipper_shutdown!("Invalid spi_angle type", Instant::now().as_ticks() as u32);
+        return;
+    }
+
+    match context.oids.entry(oid) {
+        Entry::Occupied(mut o) => {
+            log::trace!("[ANCHOR] Reconfiguring configured OID to Angle Entry");
+            let oid_entry = o.get_mut();
+            *oid_entry = OIDTypes::Angle {
+                _inner: Angle::new(spi_angle_type),
+            }
+        }
+        Entry::Vacant(v) => {
+            let _ = v.insert(OIDTypes::Angle {
+                _inner: Angle::new(spi_angle_type),
+            });
+        }
+    }
+}
+
+/// Samples the angle every `rest_ticks` starting at `clock` and streams them in `sensor_bulk_data`
+/// responses, a `clock` of 0 stops the stream
+#[klipper_command]
+pub fn query_spi_angle(
+    context: &mut crate::State,
+    oid: u8,
+    clock: u32,
+    rest_ticks: u32,
+    time_shift: u8,
+) {
+    log::trace!("[ANCHOR] Query SPI Angle - oid: {oid}, clock: {clock}, rest_ticks: {rest_ticks}, time_shift: {time_shift}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Angle { _inner } => {
+            let query = if clock == 0 {
+                None
+            } else {
+                Some(AngleQuery::new(oid, clock, rest_ticks, time_shift))
+            };
+            send_encoder_message(EncoderMessage::QueryAngle { query });
+        }
+        _ => panic!("Expected OIDType::Angle, but it is something else!"),
+    }
+}
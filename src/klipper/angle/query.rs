@@ -0,0 +1,60 @@
+use embassy_time::{Duration, Instant};
+
+use super::bulk::{SensorBulk, TCODE_ERROR};
+
+// Error codes reported in place of an angle, shared with Klipper's angle module
+pub const SE_SCHEDULE: u8 = 1;
+pub const SE_NO_ANGLE: u8 = 5;
+
+/// Periodic angle sampling started by `query_spi_angle`.
+pub struct AngleQuery {
+    next_sample: Instant,
+    rest_ticks: u32,
+    time_shift: u8,
+    bulk: SensorBulk,
+}
+
+impl AngleQuery {
+    pub fn new(oid: u8, clock: u32, rest_ticks: u32, time_shift: u8) -> Self {
+        Self {
+            next_sample: Instant::from_ticks(clock as u64),
+            rest_ticks,
+            time_shift,
+            bulk: SensorBulk::new(oid),
+        }
+    }
+
+    pub fn next_sample(&self) -> Instant {
+        self.next_sample
+    }
+
+    // Records the angle read at `measured` for the sample scheduled at `next_sample`, `None` if the
+    // sensor could not be read. `resolution` is the number of sensor angles per revolution.
+    pub fn add_sample(&mut self, measured: Instant, raw_angle: Option<u16>, resolution: u16) {
+        let scheduled = self.next_sample;
+        self.next_sample += Duration::from_ticks(self.rest_ticks as u64);
+
+        let Some(raw_angle) = raw_angle else {
+            self.bulk.add_error(SE_NO_ANGLE);
+            return;
+        };
+
+        let mut tdiff = measured.as_ticks().saturating_sub(scheduled.as_ticks()) as u32;
+        if self.time_shift != 0 {
+            tdiff = (tdiff + (1 << (self.time_shift - 1))) >> self.time_shift;
+        }
+        if tdiff >= TCODE_ERROR as u32 {
+            self.bulk.add_error(SE_SCHEDULE);
+            return;
+        }
+
+        // Scale the angle to the 16 bits per revolution the host expects
+        let angle = ((raw_angle as u32) << 16) / resolution.max(1) as u32;
+        self.bulk.add(tdiff as u8, angle as u16);
+    }
+
+    // Sends the samples still waiting for a full message
+    pub fn finish(mut self) {
+        self.bulk.report();
+    }
+}
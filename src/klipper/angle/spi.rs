@@ -0,0 +1,73 @@
+use anchor::*;
+use heapless::Entry;
+
+use crate::klipper::oid_types::*;
+
+// Longest transfer answered, the host never sends more in a single message
+const MAX_TRANSFER: usize = 64;
+
+klipper_enumeration! {
+    #[derive(Debug)]
+    #[klipper_enumeration(name = "spi_bus", rename_all = "lowercase")]
+    enum SpiBus {
+        I2c0
+    }
+}
+
+/// The host's angle module only talks to its sensor through an SPI device. The AS5600 is read by
+/// the encoder task over I2C, so the device is a placeholder that takes the configuration and
+/// answers transfers with zeros. Only devices without a chip select are supported.
+#[klipper_command]
+pub fn config_spi_without_cs(context: &mut crate::State, oid: u8) {
+    log::trace!("[ANCHOR] Config SPI Without CS - oid: {oid}");
+
+    match context.oids.entry(oid) {
+        Entry::Occupied(mut o) => {
+            log::trace!("[ANCHOR] Reconfiguring configured OID to Spi Entry");
+            let oid_entry = o.get_mut();
+            *oid_entry = OIDTypes::Spi { _inner: Spi };
+        }
+        Entry::Vacant(v) => {
+            let _ = v.insert(OIDTypes::Spi { _inner: Spi });
+        }
+    }
+}
+
+#[klipper_command]
+pub fn spi_set_bus(context: &mut crate::State, oid: u8, spi_bus: u32, mode: u32, rate: u32) {
+    log::trace!(
+        "[ANCHOR] SPI Set Bus - oid: {oid}, spi_bus: {spi_bus}, mode: {mode}, rate: {rate}"
+    );
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Spi { _inner } => {}
+        _ => panic!("Expected OIDType::Spi, but it is something else!"),
+    }
+}
+
+#[klipper_command]
+pub fn spi_send(context: &mut crate::State, oid: u8, data: &[u8]) {
+    log::trace!("[ANCHOR] SPI Send - oid: {oid}, data: {data:X?}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Spi { _inner } => {}
+        _ => panic!("Expected OIDType::Spi, but it is something else!"),
+    }
+}
+
+#[klipper_command]
+pub fn spi_transfer(context: &mut crate::State, oid: u8, data: &[u8]) {
+    log::trace!("[ANCHOR] SPI Transfer - oid: {oid}, data: {data:X?}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Spi { _inner } => {
+            let response = [0; MAX_TRANSFER];
+            klipper_reply!(
+                spi_transfer_response,
+                oid: u8 = oid,
+                response: &[u8] = &response[..data.len().min(MAX_TRANSFER)]
+            );
+        }
+        _ => panic!("Expected OIDType::Spi, but it is something else!"),
+    }
+}
@@ -3,6 +3,7 @@ use embedded_hal::digital::OutputPin;
 use heapless::Entry;
 
 use crate::klipper::oid_types::*;
+use crate::klipper::stepper::STEPPER_ENABLE_LEVEL;
 
 mod task;
 use task::execute_digital_out;
@@ -46,6 +47,10 @@ pub fn config_digital_out(
     } else {
         pin.set_low().unwrap();
     }
+    // The only digital out is the stepper enable pin
+    STEPPER_ENABLE_LEVEL.lock(|unlocked| {
+        *unlocked.borrow_mut() = Some(default_value != 0);
+    });
 
     match context.oids.entry(oid) {
         Entry::Occupied(mut o) => {
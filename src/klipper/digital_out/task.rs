@@ -1,6 +1,8 @@
 use embassy_time::{Instant, Timer};
 use embedded_hal::digital::OutputPin;
 
+use crate::klipper::stepper::STEPPER_ENABLE_LEVEL;
+
 #[embassy_executor::task]
 pub async fn execute_digital_out(
     mut pin: esp32c6_hal::gpio::GpioPin<esp32c6_hal::gpio::Output<esp32c6_hal::gpio::PushPull>, 4>,
@@ -15,4 +17,7 @@ pub async fn execute_digital_out(
     } else {
         pin.set_high().unwrap()
     }
+    STEPPER_ENABLE_LEVEL.lock(|unlocked| {
+        *unlocked.borrow_mut() = Some(on_ticks != 0);
+    });
 }
@@ -0,0 +1,118 @@
+use closed_loop::{
+    autotune::{self, StepResponse, Tuning},
+    geometry::EncoderGeometry,
+};
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Timer};
+
+use super::moves::TestMove;
+use super::task::linearize;
+use super::AngleSensor;
+
+// Readings at rest measuring the noise before the move
+const NOISE_SAMPLES: u32 = 32;
+
+// Time between readings while measuring the response
+const SAMPLE_INTERVAL: Duration = Duration::from_micros(500);
+
+#[derive(Debug)]
+pub enum AutotuneError<E> {
+    Timeout,
+    Sensor(E),
+    Tuning(autotune::Error),
+}
+
+// Reads the following error at rest, while moving `steps` forward and for `settle_ticks` after the
+// move, then moves back. The moves go through the move queue so they count towards the commanded
+// position, which ends up where it started even if a reading fails.
+pub async fn autotune<S: AngleSensor>(
+    sensor: &mut S,
+    geometry: &EncoderGeometry,
+    steps: u16,
+    step_interval: u32,
+    settle_ticks: u32,
+) -> Result<Tuning, AutotuneError<S::Error>> {
+    let steps = steps as i32;
+    let start_angle = linearize(sensor.angle().await.map_err(AutotuneError::Sensor)?.angle);
+    let mut response = StepResponse::new(steps);
+
+    for _ in 0..NOISE_SAMPLES {
+        let angle = linearize(sensor.angle().await.map_err(AutotuneError::Sensor)?.angle);
+        response.rest(following_error(geometry, start_angle, angle, 0));
+        Timer::after(SAMPLE_INTERVAL).await;
+    }
+
+    let test_move = TestMove::queue(steps, step_interval).await;
+    let measured = async {
+        let sampling = async {
+            loop {
+                let sample = match sensor.angle().await {
+                    Ok(sample) => sample,
+                    Err(e) => break e,
+                };
+                let commanded = test_move.steps_at(sample.timestamp);
+                let angle = linearize(sample.angle);
+                response.moving(following_error(geometry, start_angle, angle, commanded));
+                Timer::after(SAMPLE_INTERVAL).await;
+            }
+        };
+        match select(test_move.finish(), sampling).await {
+            Either::First(result) => result.map_err(|_| AutotuneError::Timeout)?,
+            Either::Second(e) => return Err(AutotuneError::Sensor(e)),
+        }
+
+        let end = test_move.end();
+        let settled = end + Duration::from_ticks(settle_ticks as u64);
+        loop {
+            let sample = sensor.angle().await.map_err(AutotuneError::Sensor)?;
+            let elapsed = sample
+                .timestamp
+                .checked_duration_since(end)
+                .map_or(0, |elapsed| elapsed.as_ticks() as u32);
+            let angle = linearize(sample.angle);
+            response.settling(
+                following_error(geometry, start_angle, angle, steps),
+                elapsed,
+            );
+            if sample.timestamp >= settled {
+                break;
+            }
+            Timer::after(SAMPLE_INTERVAL).await;
+        }
+        Ok::<_, AutotuneError<S::Error>>(())
+    }
+    .await;
+
+    // The move has to be through before going back, it may still be going if a reading failed
+    let finished = test_move.finish().await;
+    let returned = TestMove::queue(-steps, step_interval).await.finish().await;
+    measured?;
+    finished.and(returned).map_err(|_| AutotuneError::Timeout)?;
+
+    log::debug!(
+        "Autotune response - band : {}, peak lag : {:?}, overshoot : {}, settled after {} ticks",
+        response.band(),
+        response.peak_lag(),
+        response.overshoot(),
+        response.settle_ticks()
+    );
+    response.tune(step_interval).map_err(AutotuneError::Tuning)
+}
+
+// Encoder travel since `start_angle` against `commanded` steps, the moves stay under half a turn
+// so the travel is the shortest way around
+fn following_error(
+    geometry: &EncoderGeometry,
+    start_angle: u16,
+    angle: u16,
+    commanded: i32,
+) -> i32 {
+    let resolution = geometry.resolution() as i64;
+    let mut travel = angle as i64 - start_angle as i64;
+    if travel > resolution / 2 {
+        travel -= resolution;
+    } else if travel < -resolution / 2 {
+        travel += resolution;
+    }
+    (geometry.ticks_to_steps(travel) as i32).wrapping_sub(commanded)
+}
@@ -0,0 +1,81 @@
+use closed_loop::{backlash::Backlash, geometry::EncoderGeometry};
+use embassy_time::{Duration, Timer};
+
+use super::calibration::read_averaged_angle;
+use super::moves::TestMove;
+use super::task::linearize;
+use super::AngleSensor;
+
+#[derive(Debug)]
+pub enum BacklashError<E> {
+    Timeout,
+    Sensor(E),
+}
+
+// Moves `steps` forward to take up the slack, then reverses `cycles` times in each direction and
+// compares the encoder travel against the commanded travel. The moves go through the move queue
+// so they count towards the commanded position, which ends up where it started.
+pub async fn measure_backlash<S: AngleSensor>(
+    sensor: &mut S,
+    geometry: &EncoderGeometry,
+    steps: u16,
+    cycles: u8,
+    step_interval: u32,
+    settle_ticks: u32,
+) -> Result<Backlash, BacklashError<S::Error>> {
+    let resolution = geometry.resolution();
+    let settle = Duration::from_ticks(settle_ticks as u64);
+    // Everything is recorded in thousandths of a step
+    let commanded = steps as i32 * 1000;
+    let mut backlash = Backlash::new();
+
+    TestMove::queue(steps as i32, step_interval)
+        .await
+        .finish()
+        .await
+        .map_err(|_| BacklashError::Timeout)?;
+    Timer::after(settle).await;
+    let mut last = linearize(
+        read_averaged_angle(sensor, resolution)
+            .await
+            .map_err(BacklashError::Sensor)?,
+    );
+
+    for _ in 0..cycles {
+        for direction in [-1, 1] {
+            TestMove::queue(direction * steps as i32, step_interval)
+                .await
+                .finish()
+                .await
+                .map_err(|_| BacklashError::Timeout)?;
+            Timer::after(settle).await;
+            let angle = linearize(
+                read_averaged_angle(sensor, resolution)
+                    .await
+                    .map_err(BacklashError::Sensor)?,
+            );
+
+            let mut measured = angle as i32 - last as i32;
+            if measured > resolution as i32 / 2 {
+                measured -= resolution as i32;
+            } else if measured < -(resolution as i32) / 2 {
+                measured += resolution as i32;
+            }
+            if geometry.inverted() {
+                measured = -measured;
+            }
+            let measured = (measured as i64 * geometry.steps_per_rev() as i64 * 1000
+                / resolution as i64) as i32;
+
+            backlash.record(direction * commanded, measured);
+            last = angle;
+        }
+    }
+
+    TestMove::queue(-(steps as i32), step_interval)
+        .await
+        .finish()
+        .await
+        .map_err(|_| BacklashError::Timeout)?;
+    Ok(backlash)
+}
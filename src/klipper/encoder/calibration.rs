@@ -0,0 +1,105 @@
+use closed_loop::{
+    calibration::{self, CalibrationTable, MAX_CALIBRATION_POINTS},
+    geometry::EncoderGeometry,
+};
+use embassy_time::{Duration, Timer};
+
+use super::moves::TestMove;
+use super::task::linearize;
+use super::AngleSensor;
+
+// Number of readings averaged for every calibration point
+const CALIBRATION_READS: i32 = 8;
+
+#[derive(Debug)]
+pub enum CalibrationError<E> {
+    Timeout,
+    Sensor(E),
+    Table(calibration::Error),
+}
+
+// Steps the motor forward through one revolution and records the encoder at every point, then
+// steps back over the same points. The moves go through the move queue, so the motor and the
+// commanded position both end up where they started, even when a reading fails on the way.
+pub async fn calibrate<S: AngleSensor>(
+    sensor: &mut S,
+    geometry: &EncoderGeometry,
+    points: u16,
+    step_interval: u32,
+    settle_ticks: u32,
+) -> Result<CalibrationTable, CalibrationError<S::Error>> {
+    let steps_per_point = (geometry.steps_per_rev() / points as u32) as i32;
+    let resolution = geometry.resolution();
+    let settle = Duration::from_ticks(settle_ticks as u64);
+    let mut samples = heapless::Vec::<u16, MAX_CALIBRATION_POINTS>::new();
+    let mut moved = 0;
+
+    let recorded: Result<(), CalibrationError<S::Error>> = async {
+        for point in 0..points {
+            if point > 0 {
+                TestMove::queue(steps_per_point, step_interval)
+                    .await
+                    .finish()
+                    .await
+                    .map_err(|_| CalibrationError::Timeout)?;
+                moved += 1;
+            }
+            Timer::after(settle).await;
+            let angle = read_averaged_angle(sensor, resolution)
+                .await
+                .map_err(CalibrationError::Sensor)?;
+            samples.push(angle).map_err(|_| {
+                CalibrationError::Table(calibration::Error::PointCount(points as usize))
+            })?;
+        }
+        Ok(())
+    }
+    .await;
+
+    // One point at a time, a whole revolution can be more steps than a move holds
+    for _ in 0..moved {
+        TestMove::queue(-steps_per_point, step_interval)
+            .await
+            .finish()
+            .await
+            .map_err(|_| CalibrationError::Timeout)?;
+    }
+    recorded?;
+
+    CalibrationTable::from_samples(resolution, &samples).map_err(CalibrationError::Table)
+}
+
+pub(super) async fn read_averaged_angle<S: AngleSensor>(
+    sensor: &mut S,
+    resolution: u16,
+) -> Result<u16, S::Error> {
+    average_angle(sensor, resolution, |angle| angle).await
+}
+
+// Same as `read_averaged_angle`, in the angles the calibration table linearizes the readings to
+pub(super) async fn read_averaged_linear_angle<S: AngleSensor>(
+    sensor: &mut S,
+    resolution: u16,
+) -> Result<u16, S::Error> {
+    average_angle(sensor, resolution, linearize).await
+}
+
+async fn average_angle<S: AngleSensor>(
+    sensor: &mut S,
+    resolution: u16,
+    map: fn(u16) -> u16,
+) -> Result<u16, S::Error> {
+    let resolution = resolution as i32;
+    let first = map(sensor.angle().await?.angle) as i32;
+    let mut offset_sum = 0;
+    for _ in 1..CALIBRATION_READS {
+        let mut delta = map(sensor.angle().await?.angle) as i32 - first;
+        if delta > resolution / 2 {
+            delta -= resolution;
+        } else if delta < -resolution / 2 {
+            delta += resolution;
+        }
+        offset_sum += delta;
+    }
+    Ok((first + offset_sum / CALIBRATION_READS).rem_euclid(resolution) as u16)
+}
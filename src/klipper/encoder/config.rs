@@ -0,0 +1,210 @@
+use closed_loop::{
+    correction::CorrectionConfig, current::CurrentConfig, health::HealthLimits, hold::HoldConfig,
+    homing::RefinementConfig, stall::StallConfig,
+};
+use embassy_time::Duration;
+
+#[derive(Clone, Copy)]
+pub struct ClosedLoopConfig {
+    oid: u8,
+    correction: CorrectionConfig,
+    step_interval: u32,
+}
+
+impl ClosedLoopConfig {
+    pub fn new(oid: u8, correction: CorrectionConfig, step_interval: u32) -> Self {
+        Self {
+            oid,
+            correction,
+            step_interval,
+        }
+    }
+
+    pub fn oid(&self) -> u8 {
+        self.oid
+    }
+
+    pub fn correction(&self) -> CorrectionConfig {
+        self.correction
+    }
+
+    pub fn step_interval(&self) -> u32 {
+        self.step_interval
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct StallDetectionConfig {
+    oid: u8,
+    stall: StallConfig,
+    trigger_reason: u8,
+}
+
+impl StallDetectionConfig {
+    pub fn new(oid: u8, stall: StallConfig, trigger_reason: u8) -> Self {
+        Self {
+            oid,
+            stall,
+            trigger_reason,
+        }
+    }
+
+    pub fn oid(&self) -> u8 {
+        self.oid
+    }
+
+    pub fn stall(&self) -> StallConfig {
+        self.stall
+    }
+
+    pub fn trigger_reason(&self) -> u8 {
+        self.trigger_reason
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct IdleHoldConfig {
+    oid: u8,
+    hold: HoldConfig,
+    interval: u32,
+    step_interval: u32,
+    enable_level: bool,
+}
+
+impl IdleHoldConfig {
+    pub fn new(
+        oid: u8,
+        hold: HoldConfig,
+        interval: u32,
+        step_interval: u32,
+        enable_level: bool,
+    ) -> Self {
+        Self {
+            oid,
+            hold,
+            interval,
+            step_interval,
+            enable_level,
+        }
+    }
+
+    pub fn oid(&self) -> u8 {
+        self.oid
+    }
+
+    pub fn hold(&self) -> HoldConfig {
+        self.hold
+    }
+
+    // Ticks between readings while no steps come in to read the encoder
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    pub fn step_interval(&self) -> u32 {
+        self.step_interval
+    }
+
+    // Level of the enable pin while the driver is enabled
+    pub fn enable_level(&self) -> bool {
+        self.enable_level
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct HealthCheckConfig {
+    interval: u32,
+    limits: HealthLimits,
+}
+
+impl HealthCheckConfig {
+    // Checks every 100ms with the default limits
+    pub const DEFAULT: Self = Self {
+        interval: Duration::from_millis(100).as_ticks() as u32,
+        limits: HealthLimits::DEFAULT,
+    };
+
+    pub fn new(interval: u32, limits: HealthLimits) -> Self {
+        Self { interval, limits }
+    }
+
+    // Ticks between health checks, 0 disables them
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    pub fn limits(&self) -> HealthLimits {
+        self.limits
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CurrentPolicyConfig {
+    oid: u8,
+    slave: u8,
+    current: CurrentConfig,
+}
+
+impl CurrentPolicyConfig {
+    pub fn new(oid: u8, slave: u8, current: CurrentConfig) -> Self {
+        Self {
+            oid,
+            slave,
+            current,
+        }
+    }
+
+    pub fn oid(&self) -> u8 {
+        self.oid
+    }
+
+    // UART address of the driver
+    pub fn slave(&self) -> u8 {
+        self.slave
+    }
+
+    pub fn current(&self) -> CurrentConfig {
+        self.current
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct HomeRefinementConfig {
+    oid: u8,
+    refinement: RefinementConfig,
+    step_interval: u32,
+    settle_ticks: u32,
+}
+
+impl HomeRefinementConfig {
+    pub fn new(
+        oid: u8,
+        refinement: RefinementConfig,
+        step_interval: u32,
+        settle_ticks: u32,
+    ) -> Self {
+        Self {
+            oid,
+            refinement,
+            step_interval,
+            settle_ticks,
+        }
+    }
+
+    pub fn oid(&self) -> u8 {
+        self.oid
+    }
+
+    pub fn refinement(&self) -> RefinementConfig {
+        self.refinement
+    }
+
+    pub fn step_interval(&self) -> u32 {
+        self.step_interval
+    }
+
+    // Ticks the motor is given to come to rest before each reading
+    pub fn settle_ticks(&self) -> u32 {
+        self.settle_ticks
+    }
+}
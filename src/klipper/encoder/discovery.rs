@@ -0,0 +1,110 @@
+use closed_loop::geometry::{self, EncoderGeometry};
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, Timer};
+
+use super::moves::TestMove;
+use super::AngleSensor;
+
+// Time between readings while following the move, it has to stay under half a turn between two
+// readings
+const SAMPLE_INTERVAL: Duration = Duration::from_micros(500);
+
+#[derive(Debug)]
+pub enum DiscoveryError<E> {
+    Timeout,
+    Sensor(E),
+    Geometry(geometry::Error),
+}
+
+// Moves `steps` forward through the move queue while following the encoder, infers the direction
+// and steps per revolution from how far it turned, then moves back. The raw angle is used since a
+// calibration table or sensor range made for a wrong geometry would only get in the way.
+pub async fn discover<S: AngleSensor>(
+    sensor: &mut S,
+    steps: u16,
+    microsteps: u16,
+    step_interval: u32,
+    settle_ticks: u32,
+) -> Result<EncoderGeometry, DiscoveryError<S::Error>> {
+    let resolution = sensor.resolution();
+    let angle = sensor
+        .raw_angle()
+        .await
+        .map_err(DiscoveryError::Sensor)?
+        .angle;
+    let mut travel = Travel::new(resolution, angle);
+
+    let test_move = TestMove::queue(steps as i32, step_interval).await;
+    let measured = async {
+        let sampling = async {
+            loop {
+                match sensor.raw_angle().await {
+                    Ok(sample) => travel.update(sample.angle),
+                    Err(e) => break e,
+                }
+                Timer::after(SAMPLE_INTERVAL).await;
+            }
+        };
+        match select(test_move.finish(), sampling).await {
+            Either::First(result) => result.map_err(|_| DiscoveryError::Timeout)?,
+            Either::Second(e) => return Err(DiscoveryError::Sensor(e)),
+        }
+
+        Timer::at(test_move.end() + Duration::from_ticks(settle_ticks as u64)).await;
+        let sample = sensor.raw_angle().await.map_err(DiscoveryError::Sensor)?;
+        travel.update(sample.angle);
+        Ok::<_, DiscoveryError<S::Error>>(())
+    }
+    .await;
+
+    // The move has to be through before going back, it may still be going if a reading failed
+    let finished = test_move.finish().await;
+    let returned = TestMove::queue(-(steps as i32), step_interval)
+        .await
+        .finish()
+        .await;
+    measured?;
+    finished
+        .and(returned)
+        .map_err(|_| DiscoveryError::Timeout)?;
+
+    log::debug!(
+        "Encoder travelled {} ticks over {} steps",
+        travel.ticks,
+        steps
+    );
+    EncoderGeometry::from_travel(steps as u32, travel.ticks, microsteps, resolution)
+        .map_err(DiscoveryError::Geometry)
+}
+
+// Encoder travel across turns, taking the shortest way around between two readings
+pub(super) struct Travel {
+    resolution: i64,
+    last_angle: u16,
+    ticks: i64,
+}
+
+impl Travel {
+    pub(super) fn new(resolution: u16, angle: u16) -> Self {
+        Self {
+            resolution: resolution as i64,
+            last_angle: angle,
+            ticks: 0,
+        }
+    }
+
+    pub(super) fn update(&mut self, angle: u16) {
+        let mut delta = angle as i64 - self.last_angle as i64;
+        if delta > self.resolution / 2 {
+            delta -= self.resolution;
+        } else if delta < -self.resolution / 2 {
+            delta += self.resolution;
+        }
+        self.last_angle = angle;
+        self.ticks += delta;
+    }
+
+    pub(super) fn ticks(&self) -> i64 {
+        self.ticks
+    }
+}
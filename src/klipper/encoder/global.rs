@@ -0,0 +1,107 @@
+use core::cell::RefCell;
+
+use closed_loop::{
+    calibration::CalibrationTable,
+    estimator::{Estimate, Gains},
+    geometry::EncoderGeometry,
+    stats::ErrorWindow,
+};
+use embassy_sync::{
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex},
+    channel::Channel,
+    pubsub::PubSubChannel,
+    signal::Signal,
+};
+
+use super::{
+    ClosedLoopConfig, CurrentPolicyConfig, EncoderMessage, EncoderSample, HealthCheckConfig,
+    HealthReport, HomeRefinementConfig, IdleHoldConfig, StallDetectionConfig,
+};
+
+// Encoder readings kept for subscribers, a subscriber lagging behind loses the oldest ones
+pub const ENCODER_SAMPLES_DEPTH: usize = 32;
+pub const ENCODER_SAMPLES_SUBSCRIBERS: usize = 4;
+
+pub static TRIGGER_MAGNET_READ: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+pub static MAGNET_SENSOR: Signal<CriticalSectionRawMutex, u16> = Signal::new();
+// Last reading, `None` until the encoder has been read
+pub static ENCODER_LAST_SAMPLE: Mutex<CriticalSectionRawMutex, RefCell<Option<EncoderSample>>> =
+    Mutex::new(RefCell::new(None));
+pub static CALIBRATION_ANGLE: Signal<CriticalSectionRawMutex, u16> = Signal::new();
+
+// `None` until the host configures the closed loop for a stepper
+pub static CLOSED_LOOP_CONFIG: Mutex<CriticalSectionRawMutex, RefCell<Option<ClosedLoopConfig>>> =
+    Mutex::new(RefCell::new(None));
+
+// `None` until the host configures stall detection for a stepper
+pub static STALL_DETECTION_CONFIG: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<Option<StallDetectionConfig>>,
+> = Mutex::new(RefCell::new(None));
+
+// `None` until the host configures the idle hold for a stepper
+pub static IDLE_HOLD_CONFIG: Mutex<CriticalSectionRawMutex, RefCell<Option<IdleHoldConfig>>> =
+    Mutex::new(RefCell::new(None));
+
+// `None` leaves homing to the endstop alone
+pub static HOME_REFINEMENT_CONFIG: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<Option<HomeRefinementConfig>>,
+> = Mutex::new(RefCell::new(None));
+
+// Time between encoder readings in ticks, `None` reads the encoder after every step group
+pub static ENCODER_SAMPLE_INTERVAL: Mutex<CriticalSectionRawMutex, RefCell<Option<u32>>> =
+    Mutex::new(RefCell::new(None));
+
+pub static ENCODER_SAMPLES: PubSubChannel<
+    CriticalSectionRawMutex,
+    EncoderSample,
+    ENCODER_SAMPLES_DEPTH,
+    ENCODER_SAMPLES_SUBSCRIBERS,
+    1,
+> = PubSubChannel::new();
+
+// Fastest the encoder is expected to turn in ticks per second, `None` trusts every reading
+pub static TRACKING_MAX_VELOCITY: Mutex<CriticalSectionRawMutex, RefCell<Option<u32>>> =
+    Mutex::new(RefCell::new(None));
+
+pub static ENCODER_GEOMETRY: Mutex<CriticalSectionRawMutex, RefCell<EncoderGeometry>> =
+    Mutex::new(RefCell::new(EncoderGeometry::DEFAULT));
+
+// `None` keeps the default estimator gains
+pub static ESTIMATOR_GAINS: Mutex<CriticalSectionRawMutex, RefCell<Option<Gains>>> =
+    Mutex::new(RefCell::new(None));
+
+// Latest filtered encoder state in encoder ticks since the task started, with the clock of the
+// reading it was updated from
+pub static ENCODER_ESTIMATE: Mutex<CriticalSectionRawMutex, RefCell<(u32, Estimate)>> =
+    Mutex::new(RefCell::new((
+        0,
+        Estimate {
+            position: 0.,
+            velocity: 0.,
+            acceleration: 0.,
+        },
+    )));
+
+// `None` leaves the current to the host
+pub static CURRENT_POLICY_CONFIG: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<Option<CurrentPolicyConfig>>,
+> = Mutex::new(RefCell::new(None));
+
+// Following error statistics, accumulated until queried unless the host sets a window
+pub static FOLLOWING_ERROR_STATS: Mutex<CriticalSectionRawMutex, RefCell<ErrorWindow>> =
+    Mutex::new(RefCell::new(ErrorWindow::new(0)));
+
+pub static HEALTH_CHECK_CONFIG: Mutex<CriticalSectionRawMutex, RefCell<HealthCheckConfig>> =
+    Mutex::new(RefCell::new(HealthCheckConfig::DEFAULT));
+
+pub static ENCODER_HEALTH: Mutex<CriticalSectionRawMutex, RefCell<HealthReport>> =
+    Mutex::new(RefCell::new(HealthReport::new(0, None, None)));
+
+pub static ENCODER_CHANNEL: Channel<CriticalSectionRawMutex, EncoderMessage, 4> = Channel::new();
+
+// Linearization of the encoder, `None` until a calibration has been run
+pub static CALIBRATION_TABLE: Mutex<CriticalSectionRawMutex, RefCell<Option<CalibrationTable>>> =
+    Mutex::new(RefCell::new(None));
@@ -0,0 +1,31 @@
+use closed_loop::health::{Fault, HealthSample};
+
+// Latest health check, reported by `query_encoder_health`
+#[derive(Clone, Copy)]
+pub struct HealthReport {
+    clock: u32,
+    sample: Option<HealthSample>,
+    fault: Option<Fault>,
+}
+
+impl HealthReport {
+    pub const fn new(clock: u32, sample: Option<HealthSample>, fault: Option<Fault>) -> Self {
+        Self {
+            clock,
+            sample,
+            fault,
+        }
+    }
+
+    pub fn clock(&self) -> u32 {
+        self.clock
+    }
+
+    pub fn sample(&self) -> Option<HealthSample> {
+        self.sample
+    }
+
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+}
@@ -0,0 +1,95 @@
+use closed_loop::{geometry::EncoderGeometry, homing};
+use embassy_time::{with_timeout, Duration, Timer};
+
+use crate::klipper::stepper::{
+    StepperCorrection, STEPPER_BUSY, STEPPER_CORRECTED, STEPPER_CORRECTION, STEPPER_MOVE_QUEUE,
+    STEPPER_STOP,
+};
+
+use super::calibration::read_averaged_linear_angle;
+use super::{AngleSensor, HomeRefinementConfig};
+
+// Time between checks of the step driver while waiting for it to stop
+const STOP_POLL: Duration = Duration::from_millis(1);
+
+// Longest the step driver may take to give up the moves the endstop interrupted
+const STOP_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Extra time allowed for the refinement move on top of its step timing
+const MOVE_TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub enum HomingError<E> {
+    // The stepper was still going after the endstop triggered
+    NotStopped,
+    Timeout,
+    Sensor(E),
+    Refinement(homing::Error),
+}
+
+pub struct Refinement {
+    // Steps moved, which are not part of the commanded position
+    pub steps: i32,
+    // Steps left to the closest target after the move
+    pub error: i32,
+}
+
+// Waits for the stepper to stop after the endstop triggered, then moves to the closest target
+// angle with a correction. The step driver isn't busy between step groups, so it is only idle once
+// the rest of the interrupted move has been dropped from the queue as well. The host carries on
+// as soon as the trsync reports the trigger, so its next move can cut the correction short. Only
+// the steps that went out are reported then, and the error is what was left of the move. The
+// angles are linearized like every other reading, so the target holds across calibrations.
+pub async fn refine_home<S: AngleSensor>(
+    sensor: &mut S,
+    geometry: &EncoderGeometry,
+    config: &HomeRefinementConfig,
+) -> Result<Refinement, HomingError<S::Error>> {
+    with_timeout(STOP_TIMEOUT, async {
+        while STEPPER_BUSY.lock(|unlocked| *unlocked.borrow()) || !STEPPER_MOVE_QUEUE.is_empty() {
+            Timer::after(STOP_POLL).await;
+        }
+    })
+    .await
+    .map_err(|_| HomingError::NotStopped)?;
+    let settle = Duration::from_ticks(config.settle_ticks() as u64);
+    Timer::after(settle).await;
+
+    let refinement = config.refinement();
+    let angle = read_averaged_linear_angle(sensor, geometry.resolution())
+        .await
+        .map_err(HomingError::Sensor)?;
+    let steps = refinement
+        .steps(geometry, angle)
+        .map_err(HomingError::Refinement)?;
+
+    if steps != 0 {
+        // A trigger after the last step group is still signaled and would stop the correction
+        STEPPER_STOP.reset();
+        STEPPER_CORRECTED.reset();
+        STEPPER_CORRECTION.signal(StepperCorrection::new(steps, config.step_interval()));
+        let duration = config.step_interval() as u64 * steps.unsigned_abs() as u64;
+        let moved = with_timeout(
+            Duration::from_ticks(duration) + MOVE_TIMEOUT_MARGIN,
+            STEPPER_CORRECTED.wait(),
+        )
+        .await
+        .map_err(|_| HomingError::Timeout)?;
+        if moved != steps {
+            // Cut short, the motor may already be on the host's next move so a reading says nothing
+            return Ok(Refinement {
+                steps: moved,
+                error: steps - moved,
+            });
+        }
+        Timer::after(settle).await;
+    }
+
+    let angle = read_averaged_linear_angle(sensor, geometry.resolution())
+        .await
+        .map_err(HomingError::Sensor)?;
+    let error = match refinement.steps(geometry, angle) {
+        Ok(error) | Err(homing::Error::TooFar(error)) => error,
+    };
+    Ok(Refinement { steps, error })
+}
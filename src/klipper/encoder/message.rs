@@ -0,0 +1,57 @@
+use crate::klipper::angle::AngleQuery;
+
+use super::sensor::FilterConfig;
+
+pub enum EncoderMessage {
+    Calibrate {
+        oid: u8,
+        points: u16,
+        step_interval: u32,
+        settle_ticks: u32,
+    },
+    Autotune {
+        oid: u8,
+        steps: u16,
+        step_interval: u32,
+        settle_ticks: u32,
+        save: bool,
+    },
+    MeasureBacklash {
+        oid: u8,
+        steps: u16,
+        cycles: u8,
+        step_interval: u32,
+        settle_ticks: u32,
+    },
+    Discover {
+        oid: u8,
+        steps: u16,
+        microsteps: u16,
+        step_interval: u32,
+        settle_ticks: u32,
+        save: bool,
+    },
+    ResonanceTest {
+        oid: u8,
+        steps: u16,
+        freq_start: u16,
+        freq_end: u16,
+        samples: u16,
+        sample_ticks: u32,
+    },
+    // Sent by the endstop once it triggered
+    RefineHome,
+    // Readings for an angle endstop while it is homing, `None` once it is done
+    Homing {
+        interval: Option<u32>,
+    },
+    // `None` only reports the current settings
+    Filter {
+        oid: u8,
+        config: Option<FilterConfig>,
+    },
+    // `None` stops the angle stream
+    QueryAngle {
+        query: Option<AngleQuery>,
+    },
+}
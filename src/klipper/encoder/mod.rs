@@ -0,0 +1,810 @@
+use anchor::*;
+use closed_loop::{
+    calibration::{MAX_CALIBRATION_POINTS, MIN_CALIBRATION_POINTS},
+    correction::CorrectionConfig,
+    current::CurrentConfig,
+    estimator::Gains,
+    geometry::EncoderGeometry,
+    health::HealthLimits,
+    hold::{HoldAction, HoldConfig},
+    homing::RefinementConfig,
+    spectrum::MAX_SAMPLES,
+    stall::{StallAction, StallConfig},
+};
+
+use crate::klipper::oid_types::*;
+use crate::klipper::stepper::{STEPPER_BUSY, STEPPER_MOVE_QUEUE};
+
+mod autotune;
+mod backlash;
+mod calibration;
+mod config;
+mod discovery;
+mod global;
+mod health;
+mod homing;
+mod message;
+mod moves;
+mod resonance;
+mod sample;
+mod sensor;
+mod task;
+
+pub use config::{
+    ClosedLoopConfig, CurrentPolicyConfig, HealthCheckConfig, HomeRefinementConfig, IdleHoldConfig,
+    StallDetectionConfig,
+};
+pub use global::*;
+pub use health::HealthReport;
+pub use message::EncoderMessage;
+pub use sample::EncoderSample;
+pub use sensor::{AngleSensor, FilterConfig};
+pub use task::as5600_task;
+
+// Calibration table entries sent per `encoder_calibration_data` response
+const CALIBRATION_CHUNK: usize = 24;
+
+/// `gain` is given in thousandths, `step_interval` is the time between corrective steps in ticks and
+/// can't be 0. A `max_steps` of 0 leaves the encoder in monitoring only mode.
+#[klipper_command]
+pub fn config_closed_loop(
+    context: &mut crate::State,
+    oid: u8,
+    band: u32,
+    gain: u16,
+    max_steps: u16,
+    step_interval: u32,
+) {
+    log::trace!("[ANCHOR] Config Closed Loop - oid: {oid}, band: {band}, gain: {gain}, max_steps: {max_steps}, step_interval: {step_interval}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            if step_interval == 0 {
+                klipper_output!("[ERROR] Closed loop step interval must be above 0");
+                return;
+            }
+
+            let correction = CorrectionConfig {
+                band,
+                gain: gain as f32 / 1000.,
+                max_steps: max_steps as u32,
+            };
+
+            CLOSED_LOOP_CONFIG.lock(|unlocked| {
+                *unlocked.borrow_mut() =
+                    Some(ClosedLoopConfig::new(oid, correction, step_interval));
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Readings that need more than `max_velocity` encoder ticks per second to be explained are flagged
+/// as a possible miscount of turns, 0 trusts every reading.
+#[klipper_command]
+pub fn config_encoder_tracking(context: &mut crate::State, oid: u8, max_velocity: u32) {
+    log::trace!("[ANCHOR] Config Encoder Tracking - oid: {oid}, max_velocity: {max_velocity}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            TRACKING_MAX_VELOCITY.lock(|unlocked| {
+                *unlocked.borrow_mut() = if max_velocity == 0 {
+                    None
+                } else {
+                    Some(max_velocity)
+                };
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Declares a motor of `full_steps` per revolution driven at `microsteps`, read by an encoder of
+/// `resolution` ticks per revolution that counts down while stepping forward if `inverted` is set
+#[klipper_command]
+pub fn config_encoder_geometry(
+    context: &mut crate::State,
+    oid: u8,
+    full_steps: u16,
+    microsteps: u16,
+    resolution: u16,
+    inverted: u8,
+) {
+    log::trace!("[ANCHOR] Config Encoder Geometry - oid: {oid}, full_steps: {full_steps}, microsteps: {microsteps}, resolution: {resolution}, inverted: {inverted}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            match EncoderGeometry::new(full_steps, microsteps, resolution, inverted != 0) {
+                Ok(geometry) => ENCODER_GEOMETRY.lock(|unlocked| {
+                    *unlocked.borrow_mut() = geometry;
+                }),
+                Err(e) => {
+                    log::error!("Invalid encoder geometry : {:?}", e);
+                    klipper_output!("[ERROR] Invalid encoder geometry");
+                }
+            }
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Scales the run and hold current the host configured on the TMC2209 at UART address `slave` from
+/// `reduced` while the following error is at or below `error_low` steps to `boosted` once it
+/// reaches `error_high`, both in thousandths. The current is lowered again after `settle_samples`
+/// readings asking for less. A `reduced` of 0 hands the current back to the host. The writes are
+/// taken off the `IFCNT` the host reads back, so its own writes still check out.
+#[klipper_command]
+pub fn config_current_policy(
+    context: &mut crate::State,
+    oid: u8,
+    slave: u8,
+    reduced: u16,
+    boosted: u16,
+    error_low: u32,
+    error_high: u32,
+    settle_samples: u8,
+) {
+    log::trace!("[ANCHOR] Config Current Policy - oid: {oid}, slave: {slave}, reduced: {reduced}, boosted: {boosted}, error_low: {error_low}, error_high: {error_high}, settle_samples: {settle_samples}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let config = if reduced == 0 {
+                None
+            } else {
+                let current = CurrentConfig {
+                    reduced: reduced as f32 / 1000.,
+                    boosted: boosted as f32 / 1000.,
+                    error_low,
+                    error_high,
+                    settle_samples,
+                };
+                Some(CurrentPolicyConfig::new(oid, slave, current))
+            };
+
+            CURRENT_POLICY_CONFIG.lock(|unlocked| {
+                *unlocked.borrow_mut() = config;
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Reads the encoder every `rest_ticks` instead of after every step group, 0 goes back to reading
+/// after step groups
+#[klipper_command]
+pub fn config_encoder_sampling(context: &mut crate::State, oid: u8, rest_ticks: u32) {
+    log::trace!("[ANCHOR] Config Encoder Sampling - oid: {oid}, rest_ticks: {rest_ticks}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            ENCODER_SAMPLE_INTERVAL.lock(|unlocked| {
+                *unlocked.borrow_mut() = if rest_ticks == 0 {
+                    None
+                } else {
+                    Some(rest_ticks)
+                };
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// `response` in thousandths, from 0 for the fastest response to 999 for the smoothest estimate
+#[klipper_command]
+pub fn config_encoder_estimator(context: &mut crate::State, oid: u8, response: u16) {
+    log::trace!("[ANCHOR] Config Encoder Estimator - oid: {oid}, response: {response}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            ESTIMATOR_GAINS.lock(|unlocked| {
+                *unlocked.borrow_mut() = Some(Gains::fading_memory(response as f32 / 1000.));
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Reports the filtered encoder position in ticks, velocity in ticks per second and acceleration
+/// in ticks per second squared, as of the reading taken at `clock`
+#[klipper_command]
+pub fn query_encoder_state(context: &mut crate::State, oid: u8) {
+    log::trace!("[ANCHOR] Query Encoder State - oid: {oid}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let (clock, estimate) = ENCODER_ESTIMATE.lock(|unlocked| *unlocked.borrow());
+            klipper_reply!(
+                encoder_state,
+                oid: u8 = oid,
+                clock: u32 = clock,
+                position: i32 = estimate.position as i32,
+                velocity: i32 = estimate.velocity as i32,
+                acceleration: i32 = estimate.acceleration as i32
+            );
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Statistics are reported over windows of `window` encoder readings, a `window` of 0 accumulates
+/// readings until the statistics are queried with `reset` set
+#[klipper_command]
+pub fn config_following_error_stats(context: &mut crate::State, oid: u8, window: u32) {
+    log::trace!("[ANCHOR] Config Following Error Stats - oid: {oid}, window: {window}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            FOLLOWING_ERROR_STATS.lock(|unlocked| unlocked.borrow_mut().set_window(window));
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Reports the following error statistics in steps of the last complete window, or of the
+/// readings so far when no window completed yet. `rms` is in thousandths of a step and `bucket0` to
+/// `bucket7` count the readings of 0, 1, 2-3, 4-7, 8-15, 16-31, 32-63 and 64 or more steps.
+/// `reset` starts the statistics over once reported.
+#[klipper_command]
+pub fn query_following_error_stats(context: &mut crate::State, oid: u8, reset: u8) {
+    log::trace!("[ANCHOR] Query Following Error Stats - oid: {oid}, reset: {reset}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let (stats, complete) = FOLLOWING_ERROR_STATS.lock(|unlocked| {
+                let mut window = unlocked.borrow_mut();
+                let reported = match window.completed() {
+                    Some(stats) => (stats, true),
+                    None => (window.current(), false),
+                };
+                if reset != 0 {
+                    window.reset();
+                }
+                reported
+            });
+            let histogram = stats.histogram();
+            klipper_reply!(
+                following_error_stats,
+                oid: u8 = oid,
+                complete: u8 = complete as u8,
+                samples: u32 = stats.samples(),
+                min: i32 = stats.min(),
+                max: i32 = stats.max(),
+                rms: u32 = stats.rms_milli(),
+                bucket0: u32 = histogram[0],
+                bucket1: u32 = histogram[1],
+                bucket2: u32 = histogram[2],
+                bucket3: u32 = histogram[3],
+                bucket4: u32 = histogram[4],
+                bucket5: u32 = histogram[5],
+                bucket6: u32 = histogram[6],
+                bucket7: u32 = histogram[7]
+            );
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// `action` is 0 to ignore, 1 to warn, 2 to trigger the active trsync with `trigger_reason` and 3
+/// to shut down once the following error stays above `threshold` steps for `samples` readings.
+#[klipper_command]
+pub fn config_stall_detection(
+    context: &mut crate::State,
+    oid: u8,
+    threshold: u32,
+    samples: u8,
+    action: u8,
+    trigger_reason: u8,
+) {
+    log::trace!("[ANCHOR] Config Stall Detection - oid: {oid}, threshold: {threshold}, samples: {samples}, action: {action}, trigger_reason: {trigger_reason}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let action = match StallAction::try_from(action) {
+                Ok(action) => action,
+                Err(_) => {
+                    klipper_output!("[ERROR] Unknown stall detection action");
+                    return;
+                }
+            };
+            let stall = StallConfig {
+                threshold,
+                samples,
+                action,
+            };
+
+            STALL_DETECTION_CONFIG.lock(|unlocked| {
+                *unlocked.borrow_mut() =
+                    Some(StallDetectionConfig::new(oid, stall, trigger_reason));
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Holds the commanded position while the stepper is enabled and has no moves to go through.
+/// A following error above `threshold` steps is reported, with `action` 1 the motor is also stepped
+/// back with up to `max_steps` steps `step_interval` ticks apart per reading. Without fixed rate
+/// sampling the encoder is read every `interval` ticks while idle, 0 disables the hold.
+/// `enable_level` is the level of the enable pin while the driver is enabled.
+#[klipper_command]
+pub fn config_idle_hold(
+    context: &mut crate::State,
+    oid: u8,
+    interval: u32,
+    threshold: u32,
+    action: u8,
+    max_steps: u32,
+    step_interval: u32,
+    enable_level: u8,
+) {
+    log::trace!("[ANCHOR] Config Idle Hold - oid: {oid}, interval: {interval}, threshold: {threshold}, action: {action}, max_steps: {max_steps}, step_interval: {step_interval}, enable_level: {enable_level}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            if interval == 0 {
+                IDLE_HOLD_CONFIG.lock(|unlocked| {
+                    *unlocked.borrow_mut() = None;
+                });
+                return;
+            }
+
+            let action = match HoldAction::try_from(action) {
+                Ok(action) => action,
+                Err(_) => {
+                    klipper_output!("[ERROR] Unknown idle hold action");
+                    return;
+                }
+            };
+            let hold = HoldConfig {
+                threshold,
+                action,
+                max_steps,
+            };
+
+            IDLE_HOLD_CONFIG.lock(|unlocked| {
+                *unlocked.borrow_mut() = Some(IdleHoldConfig::new(
+                    oid,
+                    hold,
+                    interval,
+                    step_interval,
+                    enable_level != 0,
+                ));
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Once an endstop triggered and the stepper stopped, moves the motor to the closest position where
+/// the encoder reads `angle`, so homing ends at the same angle however the switch triggered. The
+/// target comes around every `period` full steps, or once per revolution with 0. The move is made
+/// of up to `max_steps` steps `step_interval` ticks apart, the motor gets `settle_ticks` to come
+/// to rest before each reading. The steps that went out are reported with `encoder_home_refined`,
+/// they leave the commanded position alone so the host has to add them to its own. A `max_steps`
+/// of 0 disables it.
+#[klipper_command]
+pub fn config_home_refinement(
+    context: &mut crate::State,
+    oid: u8,
+    angle: u16,
+    period: u16,
+    max_steps: u32,
+    step_interval: u32,
+    settle_ticks: u32,
+) {
+    log::trace!("[ANCHOR] Config Home Refinement - oid: {oid}, angle: {angle}, period: {period}, max_steps: {max_steps}, step_interval: {step_interval}, settle_ticks: {settle_ticks}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            if max_steps == 0 {
+                HOME_REFINEMENT_CONFIG.lock(|unlocked| {
+                    *unlocked.borrow_mut() = None;
+                });
+                return;
+            }
+
+            let resolution = ENCODER_GEOMETRY.lock(|unlocked| unlocked.borrow().resolution());
+            if angle >= resolution {
+                klipper_output!("[ERROR] Home refinement angle is beyond the encoder resolution");
+                return;
+            }
+
+            let refinement = RefinementConfig {
+                angle,
+                period,
+                max_steps,
+            };
+            HOME_REFINEMENT_CONFIG.lock(|unlocked| {
+                *unlocked.borrow_mut() = Some(HomeRefinementConfig::new(
+                    oid,
+                    refinement,
+                    step_interval,
+                    settle_ticks,
+                ));
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Checks the magnet every `interval` ticks, 0 disables the checks. The gain control is saturated
+/// at or beyond `gain_min` and `gain_max`, which depend on the supply voltage of the sensor. The
+/// MT6701 has neither a gain nor a magnitude reading, its limits are best left at their defaults.
+/// Over I2C it doesn't report its field either, the checks then only make sure the sensor answers.
+/// A fault seen on `warn_samples` consecutive checks is reported, one seen on `shutdown_samples`
+/// shuts the MCU down unless it is 0.
+#[klipper_command]
+pub fn config_encoder_health(
+    context: &mut crate::State,
+    oid: u8,
+    interval: u32,
+    gain_min: u8,
+    gain_max: u8,
+    magnitude_min: u16,
+    magnitude_max: u16,
+    warn_samples: u8,
+    shutdown_samples: u8,
+) {
+    log::trace!("[ANCHOR] Config Encoder Health - oid: {oid}, interval: {interval}, gain_min: {gain_min}, gain_max: {gain_max}, magnitude_min: {magnitude_min}, magnitude_max: {magnitude_max}, warn_samples: {warn_samples}, shutdown_samples: {shutdown_samples}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let limits = HealthLimits {
+                gain_min,
+                gain_max,
+                magnitude_min,
+                magnitude_max,
+                warn_samples,
+                shutdown_samples,
+            };
+
+            HEALTH_CHECK_CONFIG.lock(|unlocked| {
+                *unlocked.borrow_mut() = HealthCheckConfig::new(interval, limits);
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Reports the latest health check, `fault` is 0 while healthy. `magnet`, `gain` and `magnitude`
+/// are 0 when the sensor could not be read, `magnet` is 4 when the sensor does not report its field
+#[klipper_command]
+pub fn query_encoder_health(context: &mut crate::State, oid: u8) {
+    log::trace!("[ANCHOR] Query Encoder Health - oid: {oid}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let report = ENCODER_HEALTH.lock(|unlocked| *unlocked.borrow());
+            let (magnet, gain, magnitude) = match report.sample() {
+                Some(sample) => (sample.magnet.into(), sample.gain, sample.magnitude),
+                None => (0, 0, 0),
+            };
+            klipper_reply!(
+                encoder_health,
+                oid: u8 = oid,
+                clock: u32 = report.clock(),
+                magnet: u8 = magnet,
+                gain: u8 = gain,
+                magnitude: u16 = magnitude,
+                fault: u8 = report.fault().map_or(0, u8::from)
+            );
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Walks the motor through one revolution in `points` equal increments and records the encoder at
+/// each point, then walks it back to where it started. The resulting table linearizes every
+/// following encoder reading. It takes 4 to 256 points, with fewer the encoder can't tell which way
+/// around it went between two of them.
+#[klipper_command]
+pub fn encoder_calibrate(
+    context: &mut crate::State,
+    oid: u8,
+    points: u16,
+    step_interval: u32,
+    settle_ticks: u32,
+) {
+    log::trace!("[ANCHOR] Encoder Calibrate - oid: {oid}, points: {points}, step_interval: {step_interval}, settle_ticks: {settle_ticks}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let steps_per_rev = ENCODER_GEOMETRY.lock(|unlocked| unlocked.borrow().steps_per_rev());
+            if !(MIN_CALIBRATION_POINTS..=MAX_CALIBRATION_POINTS).contains(&(points as usize))
+                || steps_per_rev % points as u32 != 0
+            {
+                klipper_output!("[ERROR] Calibration needs 4 to 256 points evenly dividing a turn");
+                return;
+            }
+
+            if !stepper_idle() {
+                klipper_output!("[ERROR] Encoder calibration requires an idle stepper");
+                return;
+            }
+
+            send_encoder_message(EncoderMessage::Calibrate {
+                oid,
+                points,
+                step_interval,
+                settle_ticks,
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Moves the motor `steps` forward and back through the move queue and computes the correction
+/// settings and stall threshold from the encoder response, they are applied right away and stored
+/// along with the rest of the encoder settings when `save` is set. `steps` has to stay under a
+/// quarter revolution, `settle_ticks` is how long the response is followed after the move.
+#[klipper_command]
+pub fn encoder_autotune(
+    context: &mut crate::State,
+    oid: u8,
+    steps: u16,
+    step_interval: u32,
+    settle_ticks: u32,
+    save: u8,
+) {
+    log::trace!("[ANCHOR] Encoder Autotune - oid: {oid}, steps: {steps}, step_interval: {step_interval}, settle_ticks: {settle_ticks}, save: {save}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let steps_per_rev = ENCODER_GEOMETRY.lock(|unlocked| unlocked.borrow().steps_per_rev());
+            if steps == 0 || steps as u32 * 4 >= steps_per_rev {
+                klipper_output!("[ERROR] Autotune steps must be under a quarter revolution");
+                return;
+            }
+
+            if !stepper_idle() {
+                klipper_output!("[ERROR] Encoder autotune requires an idle stepper");
+                return;
+            }
+
+            send_encoder_message(EncoderMessage::Autotune {
+                oid,
+                steps,
+                step_interval,
+                settle_ticks,
+                save: save != 0,
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Moves the motor `steps` back and forth `cycles` times through the move queue and reports the
+/// lost motion on reversal in thousandths of a step and of a degree. `steps` has to stay under a
+/// quarter revolution so the encoder travel can't be mistaken for the other way around.
+#[klipper_command]
+pub fn encoder_measure_backlash(
+    context: &mut crate::State,
+    oid: u8,
+    steps: u16,
+    cycles: u8,
+    step_interval: u32,
+    settle_ticks: u32,
+) {
+    log::trace!("[ANCHOR] Encoder Measure Backlash - oid: {oid}, steps: {steps}, cycles: {cycles}, step_interval: {step_interval}, settle_ticks: {settle_ticks}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let steps_per_rev = ENCODER_GEOMETRY.lock(|unlocked| unlocked.borrow().steps_per_rev());
+            if steps == 0 || cycles == 0 || steps as u32 * 4 >= steps_per_rev {
+                klipper_output!("[ERROR] Backlash steps must be under a quarter revolution");
+                return;
+            }
+
+            if !stepper_idle() {
+                klipper_output!("[ERROR] Backlash measurement requires an idle stepper");
+                return;
+            }
+
+            send_encoder_message(EncoderMessage::MeasureBacklash {
+                oid,
+                steps,
+                cycles,
+                step_interval,
+                settle_ticks,
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Moves the motor `steps` forward and back through the move queue at `microsteps` and infers from
+/// the encoder travel whether it counts down while stepping forward and how many full steps make a
+/// revolution. The result replaces the encoder geometry, reported with `encoder_discovery_result`
+/// and stored along with the rest of the encoder settings when `save` is set. A longer move gives a
+/// more precise measurement, `step_interval` has to keep the motor under half a turn per 500µs.
+#[klipper_command]
+pub fn encoder_discover(
+    context: &mut crate::State,
+    oid: u8,
+    steps: u16,
+    microsteps: u16,
+    step_interval: u32,
+    settle_ticks: u32,
+    save: u8,
+) {
+    log::trace!("[ANCHOR] Encoder Discover - oid: {oid}, steps: {steps}, microsteps: {microsteps}, step_interval: {step_interval}, settle_ticks: {settle_ticks}, save: {save}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            if steps == 0 {
+                klipper_output!("[ERROR] Encoder discovery needs steps to move");
+                return;
+            }
+
+            if !stepper_idle() {
+                klipper_output!("[ERROR] Encoder discovery requires an idle stepper");
+                return;
+            }
+
+            send_encoder_message(EncoderMessage::Discover {
+                oid,
+                steps,
+                microsteps,
+                step_interval,
+                settle_ticks,
+                save: save != 0,
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Sweeps the motor `steps` back and forth through the move queue, going from `freq_start` to
+/// `freq_end` oscillations per second, while recording the following error every `sample_ticks`.
+/// The sweep lasts as long as the capture of `samples` readings, a power of two from 16 to 1024.
+/// The strongest frequencies in the following error are reported with `encoder_resonance_peak`,
+/// in mHz and thousandths of a step, followed by `encoder_resonance_result`. `steps` has to stay
+/// under a quarter revolution so the encoder travel can't be mistaken for the other way around.
+#[klipper_command]
+pub fn encoder_resonance_test(
+    context: &mut crate::State,
+    oid: u8,
+    steps: u16,
+    freq_start: u16,
+    freq_end: u16,
+    samples: u16,
+    sample_ticks: u32,
+) {
+    log::trace!("[ANCHOR] Encoder Resonance Test - oid: {oid}, steps: {steps}, freq_start: {freq_start}, freq_end: {freq_end}, samples: {samples}, sample_ticks: {sample_ticks}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let steps_per_rev = ENCODER_GEOMETRY.lock(|unlocked| unlocked.borrow().steps_per_rev());
+            if steps == 0 || steps as u32 * 4 >= steps_per_rev {
+                klipper_output!("[ERROR] Resonance test steps must be under a quarter revolution");
+                return;
+            }
+
+            if freq_start == 0 || freq_end < freq_start {
+                klipper_output!("[ERROR] Resonance test frequencies must go up from above 0");
+                return;
+            }
+
+            if !(16..=MAX_SAMPLES).contains(&(samples as usize))
+                || !samples.is_power_of_two()
+                || sample_ticks == 0
+            {
+                klipper_output!("[ERROR] Resonance test samples must be a power of two to 1024");
+                return;
+            }
+
+            if !stepper_idle() {
+                klipper_output!("[ERROR] Resonance test requires an idle stepper");
+                return;
+            }
+
+            send_encoder_message(EncoderMessage::ResonanceTest {
+                oid,
+                steps,
+                freq_start,
+                freq_end,
+                samples,
+                sample_ticks,
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Sets the sensor filtering, with the field values of the AS5600 `CONF` register. `slow_filter`
+/// goes from 16x (0) to 2x (3), `fast_filter_threshold` from slow filter only (0) to 10 LSB (7),
+/// `hysteresis` from off (0) to 3 LSB (3) and `power_mode` from normal (0) to LPM3 (3). Stronger
+/// filtering lowers the noise at the cost of latency. The settings in use are reported back with
+/// `encoder_filter`.
+#[klipper_command]
+pub fn config_encoder_filter(
+    context: &mut crate::State,
+    oid: u8,
+    slow_filter: u8,
+    fast_filter_threshold: u8,
+    hysteresis: u8,
+    power_mode: u8,
+) {
+    log::trace!("[ANCHOR] Config Encoder Filter - oid: {oid}, slow_filter: {slow_filter}, fast_filter_threshold: {fast_filter_threshold}, hysteresis: {hysteresis}, power_mode: {power_mode}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let config = FilterConfig {
+                slow_filter,
+                fast_filter_threshold,
+                hysteresis,
+                power_mode,
+            };
+            send_encoder_message(EncoderMessage::Filter {
+                oid,
+                config: Some(config),
+            });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Reports the sensor filtering with `encoder_filter`
+#[klipper_command]
+pub fn query_encoder_filter(context: &mut crate::State, oid: u8) {
+    log::trace!("[ANCHOR] Query Encoder Filter - oid: {oid}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            send_encoder_message(EncoderMessage::Filter { oid, config: None });
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Reports up to `CALIBRATION_CHUNK` table entries starting at `offset`, as little endian u16s
+#[klipper_command]
+pub fn query_encoder_calibration(context: &mut crate::State, oid: u8, offset: u16) {
+    log::trace!("[ANCHOR] Query Encoder Calibration - oid: {oid}, offset: {offset}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => CALIBRATION_TABLE.lock(|unlocked| {
+            let mut data = [0u8; CALIBRATION_CHUNK * 2];
+            let (origin, inverted, points, length) = match unlocked.borrow().as_ref() {
+                Some(table) => {
+                    let entries = table
+                        .offsets()
+                        .iter()
+                        .skip(offset as usize)
+                        .take(CALIBRATION_CHUNK);
+                    let mut length = 0;
+                    for (bytes, entry) in data.chunks_exact_mut(2).zip(entries) {
+                        bytes.copy_from_slice(&entry.to_le_bytes());
+                        length += 2;
+                    }
+                    (table.origin(), table.inverted(), table.points(), length)
+                }
+                None => (0, false, 0, 0),
+            };
+
+            klipper_reply!(
+                encoder_calibration_data,
+                oid: u8 = oid,
+                origin: u16 = origin,
+                inverted: u8 = inverted as u8,
+                points: u16 = points,
+                offset: u16 = offset,
+                data: &[u8] = &data[..length]
+            );
+        }),
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+// The step group going out has already left the move queue, so the step driver has to be idle too
+fn stepper_idle() -> bool {
+    STEPPER_MOVE_QUEUE.is_empty() && !STEPPER_BUSY.lock(|unlocked| *unlocked.borrow())
+}
+
+// The encoder task doesn't take messages while it runs a routine, waiting for room from a command
+// would hold up every other command until it is done
+pub fn send_encoder_message(message: EncoderMessage) {
+    if ENCODER_CHANNEL.try_send(message).is_err() {
+        klipper_output!("[ERROR] Encoder is busy, try again once it is done");
+    }
+}
@@ -0,0 +1,140 @@
+use embassy_time::{with_timeout, Duration, Instant, TimeoutError, TICK_HZ};
+
+use crate::klipper::stepper::{StepInfo, StepperMessage, STEPPER_MOVE_QUEUE, STEPPER_POSITION};
+
+use super::TRIGGER_MAGNET_READ;
+
+// Time between queueing a move and its first step
+const MOVE_START_DELAY: Duration = Duration::from_millis(10);
+
+// Extra time allowed for a move on top of its step timing before giving up on it
+const MOVE_TIMEOUT_MARGIN: Duration = Duration::from_millis(100);
+
+// Constant rate move in a single direction, queued by the encoder routines. It goes through the
+// move queue like the host's moves do, so it counts towards the commanded position.
+pub struct TestMove {
+    steps: i32,
+    step_interval: u32,
+    start: Instant,
+    target: i32,
+}
+
+impl TestMove {
+    pub async fn queue(steps: i32, step_interval: u32) -> Self {
+        let target = STEPPER_POSITION.lock(|unlocked| unlocked.borrow().wrapping_add(steps));
+        let count = steps.unsigned_abs() as u16;
+        let start = queue_first_step(steps > 0).await;
+        queue_steps(step_interval, count.saturating_sub(1), steps > 0).await;
+
+        Self {
+            steps,
+            step_interval,
+            start,
+            target,
+        }
+    }
+
+    // Steps that went out by `at` according to the schedule
+    pub fn steps_at(&self, at: Instant) -> i32 {
+        if at < self.start {
+            return 0;
+        }
+        let done = at.duration_since(self.start).as_ticks() / self.step_interval.max(1) as u64 + 1;
+        done.min(self.steps.unsigned_abs() as u64) as i32 * self.steps.signum()
+    }
+
+    // Time of the last step
+    pub fn end(&self) -> Instant {
+        let remaining = self.steps.unsigned_abs().saturating_sub(1) as u64;
+        self.start + Duration::from_ticks(self.step_interval as u64 * remaining)
+    }
+
+    // Waits for the step driver to go through the whole move
+    pub async fn finish(&self) -> Result<(), TimeoutError> {
+        wait_position(self.target, self.end()).await
+    }
+}
+
+// Back and forth moves of `steps` at a rate going from `freq_start` to `freq_end` oscillations per
+// second over `duration`. Only whole oscillations are queued so the motor ends where it started.
+pub struct Sweep {
+    end: Instant,
+    target: i32,
+}
+
+impl Sweep {
+    pub async fn queue(steps: u16, freq_start: u16, freq_end: u16, duration: Duration) -> Self {
+        let target = STEPPER_POSITION.lock(|unlocked| *unlocked.borrow());
+        let start = queue_first_step(true).await;
+
+        let duration = duration.as_ticks();
+        let mut elapsed = 0;
+        let mut forward = true;
+        let mut count = steps - 1;
+        loop {
+            let progress = elapsed.min(duration) as f32 / duration.max(1) as f32;
+            let frequency = freq_start as f32 + (freq_end as f32 - freq_start as f32) * progress;
+            // Half an oscillation each way
+            let interval = ((TICK_HZ as f32 / (2. * frequency * steps as f32)) as u32).max(1);
+            queue_steps(interval, count, forward).await;
+            elapsed += interval as u64 * steps as u64;
+
+            forward = !forward;
+            count = steps;
+            if forward && elapsed >= duration {
+                break;
+            }
+        }
+
+        Self {
+            end: start + Duration::from_ticks(elapsed),
+            target,
+        }
+    }
+
+    // Waits for the step driver to go through the whole sweep
+    pub async fn finish(&self) -> Result<(), TimeoutError> {
+        wait_position(self.target, self.end).await
+    }
+}
+
+// Queues the first step of a move, returning when it goes out. The step driver drops steps that
+// are scheduled in the past, so it starts a little later.
+async fn queue_first_step(dir: bool) -> Instant {
+    let start = Instant::now() + MOVE_START_DELAY;
+
+    TRIGGER_MAGNET_READ.reset();
+    // After a clock reset the first interval is the absolute clock of the first step
+    STEPPER_MOVE_QUEUE
+        .send(StepperMessage::ResetStepClock)
+        .await;
+    STEPPER_MOVE_QUEUE
+        .send(StepperMessage::StepInfo {
+            _inner: StepInfo::new(start.as_ticks() as u32, 1, 0, dir),
+        })
+        .await;
+    start
+}
+
+async fn queue_steps(interval: u32, count: u16, dir: bool) {
+    if count > 0 {
+        STEPPER_MOVE_QUEUE
+            .send(StepperMessage::StepInfo {
+                _inner: StepInfo::new(interval, count, 0, dir),
+            })
+            .await;
+    }
+}
+
+// Waits for the step driver to reach `target`, with some time to spare after `end`
+async fn wait_position(target: i32, end: Instant) -> Result<(), TimeoutError> {
+    let timeout = (end + MOVE_TIMEOUT_MARGIN)
+        .checked_duration_since(Instant::now())
+        .unwrap_or(Duration::from_ticks(0));
+    with_timeout(timeout, async {
+        while STEPPER_POSITION.lock(|unlocked| *unlocked.borrow()) != target {
+            TRIGGER_MAGNET_READ.wait().await;
+        }
+    })
+    .await
+}
@@ -0,0 +1,105 @@
+use closed_loop::{
+    geometry::EncoderGeometry,
+    spectrum::{self, Peak, MAX_SAMPLES},
+};
+use embassy_futures::join::join;
+use embassy_time::{Duration, Instant, Timer, TICK_HZ};
+
+use crate::klipper::stepper::STEPPER_POSITION;
+
+use super::discovery::Travel;
+use super::moves::Sweep;
+use super::task::linearize;
+use super::AngleSensor;
+
+// Strongest frequencies reported back to the host
+pub const RESONANCE_PEAKS: usize = 5;
+
+#[derive(Debug)]
+pub enum ResonanceError<E> {
+    Timeout,
+    Sensor(E),
+    // A reading took longer than the time between samples
+    Overrun,
+    Spectrum(spectrum::Error),
+}
+
+pub struct Resonance {
+    // Width of a frequency bin, in Hz
+    pub bin_width: f32,
+    pub peaks: [Peak; RESONANCE_PEAKS],
+    pub found: usize,
+}
+
+// Sweeps the motor `steps` back and forth from `freq_start` to `freq_end` oscillations per second
+// through the move queue, recording the following error every `sample_ticks` for as long as the
+// sweep lasts, then looks for the frequencies it oscillates at. The samples have to be taken at a
+// steady rate for the spectrum to mean anything, so the capture fails if a reading runs late.
+pub async fn resonance_test<S: AngleSensor>(
+    sensor: &mut S,
+    geometry: &EncoderGeometry,
+    steps: u16,
+    freq_start: u16,
+    freq_end: u16,
+    samples: u16,
+    sample_ticks: u32,
+) -> Result<Resonance, ResonanceError<S::Error>> {
+    let count = samples as usize;
+    let interval = Duration::from_ticks(sample_ticks as u64);
+    let mut following_error = [0f32; MAX_SAMPLES];
+    let mut scratch = [0f32; MAX_SAMPLES];
+
+    // Kept in fractions of a step, the oscillations can be a lot smaller than one
+    let mut steps_per_tick = geometry.steps_per_rev() as f32 / geometry.resolution() as f32;
+    if geometry.inverted() {
+        steps_per_tick = -steps_per_tick;
+    }
+    let start_position = STEPPER_POSITION.lock(|unlocked| *unlocked.borrow());
+    let mut travel = Travel::new(
+        geometry.resolution(),
+        linearize(sensor.angle().await.map_err(ResonanceError::Sensor)?.angle),
+    );
+
+    let capture = async {
+        let mut next = Instant::now();
+        for error in following_error[..count].iter_mut() {
+            Timer::at(next).await;
+            let reading = sensor.angle().await.map_err(ResonanceError::Sensor)?;
+            travel.update(linearize(reading.angle));
+            let commanded =
+                STEPPER_POSITION.lock(|unlocked| unlocked.borrow().wrapping_sub(start_position));
+            *error = travel.ticks() as f32 * steps_per_tick - commanded as f32;
+
+            next += interval;
+            if Instant::now() > next {
+                return Err(ResonanceError::Overrun);
+            }
+        }
+        Ok::<_, ResonanceError<S::Error>>(())
+    };
+    let sweep = Sweep::queue(steps, freq_start, freq_end, interval * samples as u32);
+    let (sweep, captured) = join(sweep, capture).await;
+    // The motor gets back to where it started even if the capture failed
+    let finished = sweep.finish().await;
+    captured?;
+    finished.map_err(|_| ResonanceError::Timeout)?;
+
+    let mut peaks = [Peak {
+        frequency: 0.,
+        amplitude: 0.,
+    }; RESONANCE_PEAKS];
+    let sample_rate = TICK_HZ as f32 / sample_ticks as f32;
+    let found = spectrum::analyze(
+        &mut following_error[..count],
+        &mut scratch[..count],
+        sample_rate,
+        &mut peaks,
+    )
+    .map_err(ResonanceError::Spectrum)?;
+
+    Ok(Resonance {
+        bin_width: sample_rate / count as f32,
+        peaks,
+        found,
+    })
+}
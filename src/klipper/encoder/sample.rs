@@ -0,0 +1,39 @@
+// Encoder reading published by the encoder task on `ENCODER_SAMPLES`
+#[derive(Clone, Copy, Debug)]
+pub struct EncoderSample {
+    clock: u32,
+    angle: u16,
+    position: i64,
+    following_error: i32,
+}
+
+impl EncoderSample {
+    pub fn new(clock: u32, angle: u16, position: i64, following_error: i32) -> Self {
+        Self {
+            clock,
+            angle,
+            position,
+            following_error,
+        }
+    }
+
+    // Clock the sensor sampled the angle at
+    pub fn clock(&self) -> u32 {
+        self.clock
+    }
+
+    // Linearized angle within one revolution, in encoder ticks
+    pub fn angle(&self) -> u16 {
+        self.angle
+    }
+
+    // Encoder ticks travelled since the encoder task started
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    // Measured minus commanded position, in steps
+    pub fn following_error(&self) -> i32 {
+        self.following_error
+    }
+}
@@ -0,0 +1,234 @@
+use core::fmt::Debug;
+
+use as5047_async::As5047;
+use as5600_async::{
+    configuration::{FastFilterThreshold, Hysteresis, PowerMode, SlowFilterMode},
+    error::Error,
+    status::Status,
+    As5600,
+};
+use closed_loop::health::{HealthSample, MagnetState};
+use embassy_time::Instant;
+use embedded_hal_async::{i2c::I2c, spi::SpiDevice};
+use mt6701_async::{
+    ssi::{Field, Mt6701Ssi},
+    Mt6701,
+};
+
+// The MT6701 has no gain control or magnitude readout, its health samples sit within the default
+// limits so only the field status counts
+const MT6701_GAIN: u8 = 128;
+const MT6701_MAGNITUDE: u16 = 0;
+
+/// Angle reading along with the time the sensor sampled it.
+#[derive(Debug, Clone, Copy)]
+pub struct AngleSample {
+    pub angle: u16,
+    pub timestamp: Instant,
+}
+
+/// Filter, hysteresis and power settings, with the field encoding of the AS5600 `CONF` register.
+#[derive(Debug, Clone, Copy)]
+pub struct FilterConfig {
+    pub slow_filter: u8,
+    pub fast_filter_threshold: u8,
+    pub hysteresis: u8,
+    pub power_mode: u8,
+}
+
+/// Magnetic angle sensor the encoder task can run on.
+#[allow(async_fn_in_trait)]
+pub trait AngleSensor {
+    type Error: Debug;
+
+    /// Angle positions per revolution.
+    fn resolution(&self) -> u16;
+
+    /// Angle with the sensor's own zero position and range applied.
+    async fn angle(&mut self) -> Result<AngleSample, Self::Error>;
+
+    /// Angle as measured, before any of the sensor's own adjustments.
+    async fn raw_angle(&mut self) -> Result<AngleSample, Self::Error>;
+
+    /// Magnet and signal strength readings.
+    async fn status(&mut self) -> Result<HealthSample, Self::Error>;
+
+    /// Current filter settings, `None` when the sensor has none to change.
+    async fn filter_config(&mut self) -> Result<Option<FilterConfig>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Replace the filter settings, returns `false` when the sensor has none to change.
+    async fn set_filter_config(&mut self, _config: FilterConfig) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+impl<E: Debug, I2C: I2c<Error = E>> AngleSensor for As5600<I2C> {
+    type Error = Error<E>;
+
+    fn resolution(&self) -> u16 {
+        as5600_async::constants::RESOLUTION
+    }
+
+    // The angle is converted continuously, a read returns the conversion done as it starts
+    async fn angle(&mut self) -> Result<AngleSample, Self::Error> {
+        let timestamp = Instant::now();
+        let angle = As5600::angle(self).await?;
+        Ok(AngleSample { angle, timestamp })
+    }
+
+    async fn raw_angle(&mut self) -> Result<AngleSample, Self::Error> {
+        let timestamp = Instant::now();
+        let angle = As5600::raw_angle(self).await?;
+        Ok(AngleSample { angle, timestamp })
+    }
+
+    async fn status(&mut self) -> Result<HealthSample, Self::Error> {
+        // A status without any of the magnet bits set does not parse
+        let magnet = match self.magnet_status().await {
+            Ok(Status::MagnetDetected) => MagnetState::Detected,
+            Ok(Status::MagnetDetectedHigh) | Ok(Status::MagnetHigh) => MagnetState::TooStrong,
+            Ok(Status::MagnetDetectedLow) | Ok(Status::MagnetLow) => MagnetState::TooWeak,
+            Err(Error::Status(_)) => MagnetState::Missing,
+            Err(e) => return Err(e),
+        };
+        let gain = self.automatic_gain_control().await?;
+        let magnitude = self.magnitude().await?;
+
+        Ok(HealthSample {
+            magnet,
+            gain,
+            magnitude,
+        })
+    }
+
+    async fn filter_config(&mut self) -> Result<Option<FilterConfig>, Self::Error> {
+        let config = self.config().await?;
+        Ok(Some(FilterConfig {
+            slow_filter: config.slow_filter.into(),
+            fast_filter_threshold: config.fast_filter_threshold.into(),
+            hysteresis: config.hysteresis.into(),
+            power_mode: config.power_mode.into(),
+        }))
+    }
+
+    // The output stage, PWM frequency and watchdog are left as they are
+    async fn set_filter_config(&mut self, filter: FilterConfig) -> Result<bool, Self::Error> {
+        let mut config = self.config().await?;
+        config.slow_filter =
+            SlowFilterMode::try_from(filter.slow_filter).map_err(Error::Configuration)?;
+        config.fast_filter_threshold = FastFilterThreshold::try_from(filter.fast_filter_threshold)
+            .map_err(Error::Configuration)?;
+        config.hysteresis =
+            Hysteresis::try_from(filter.hysteresis).map_err(Error::Configuration)?;
+        config.power_mode = PowerMode::try_from(filter.power_mode).map_err(Error::Configuration)?;
+        self.set_config(config).await?;
+        Ok(true)
+    }
+}
+
+impl<E: Debug, SPI: SpiDevice<Error = E>> AngleSensor for As5047<SPI> {
+    type Error = as5047_async::error::Error<E>;
+
+    fn resolution(&self) -> u16 {
+        as5047_async::constants::RESOLUTION
+    }
+
+    // The angle is latched as the chip select falls on the command frame
+    async fn angle(&mut self) -> Result<AngleSample, Self::Error> {
+        let timestamp = Instant::now();
+        let angle = As5047::angle(self).await?;
+        Ok(AngleSample { angle, timestamp })
+    }
+
+    async fn raw_angle(&mut self) -> Result<AngleSample, Self::Error> {
+        let timestamp = Instant::now();
+        let angle = As5047::raw_angle(self).await?;
+        Ok(AngleSample { angle, timestamp })
+    }
+
+    async fn status(&mut self) -> Result<HealthSample, Self::Error> {
+        let diagnostics = self.diagnostics().await?;
+        let magnet = if !diagnostics.is_valid() {
+            MagnetState::Missing
+        } else if diagnostics.magnet_low {
+            MagnetState::TooWeak
+        } else if diagnostics.magnet_high {
+            MagnetState::TooStrong
+        } else {
+            MagnetState::Detected
+        };
+        let magnitude = self.magnitude().await?;
+
+        Ok(HealthSample {
+            magnet,
+            gain: diagnostics.agc,
+            magnitude,
+        })
+    }
+}
+
+impl<E: Debug, I2C: I2c<Error = E>> AngleSensor for Mt6701<I2C> {
+    type Error = mt6701_async::error::Error<E>;
+
+    fn resolution(&self) -> u16 {
+        mt6701_async::constants::RESOLUTION
+    }
+
+    async fn angle(&mut self) -> Result<AngleSample, Self::Error> {
+        let timestamp = Instant::now();
+        let angle = Mt6701::angle(self).await?;
+        Ok(AngleSample { angle, timestamp })
+    }
+
+    // There is no angle register without the zero position applied
+    async fn raw_angle(&mut self) -> Result<AngleSample, Self::Error> {
+        AngleSensor::angle(self).await
+    }
+
+    // The field status only comes with the SSI frame, all that can be checked here is that the
+    // sensor answers
+    async fn status(&mut self) -> Result<HealthSample, Self::Error> {
+        Mt6701::angle(self).await?;
+        Ok(HealthSample {
+            magnet: MagnetState::Unknown,
+            gain: MT6701_GAIN,
+            magnitude: MT6701_MAGNITUDE,
+        })
+    }
+}
+
+impl<E: Debug, SPI: SpiDevice<Error = E>> AngleSensor for Mt6701Ssi<SPI> {
+    type Error = mt6701_async::error::Error<E>;
+
+    fn resolution(&self) -> u16 {
+        mt6701_async::constants::RESOLUTION
+    }
+
+    // The angle is latched as the chip select falls
+    async fn angle(&mut self) -> Result<AngleSample, Self::Error> {
+        let timestamp = Instant::now();
+        let angle = Mt6701Ssi::angle(self).await?;
+        Ok(AngleSample { angle, timestamp })
+    }
+
+    // The SSI frame only carries the angle with the zero position applied
+    async fn raw_angle(&mut self) -> Result<AngleSample, Self::Error> {
+        AngleSensor::angle(self).await
+    }
+
+    async fn status(&mut self) -> Result<HealthSample, Self::Error> {
+        let magnet = match self.sample().await?.status.field {
+            Field::Normal => MagnetState::Detected,
+            Field::TooStrong => MagnetState::TooStrong,
+            Field::TooWeak => MagnetState::TooWeak,
+        };
+
+        Ok(HealthSample {
+            magnet,
+            gain: MT6701_GAIN,
+            magnitude: MT6701_MAGNITUDE,
+        })
+    }
+}
@@ -0,0 +1,832 @@
+use core::time::Duration;
+
+use anchor::*;
+use as5600_async::{multi_turn::MultiTurnTracker, As5600};
+use closed_loop::{
+    correction::Corrector,
+    current::{CurrentPolicy, IholdIrun, IHOLD_IRUN},
+    estimator::Estimator,
+    geometry::EncoderGeometry,
+    health::{Fault, HealthEvent, HealthMonitor, HealthSample, MagnetState},
+    hold::{HoldEvent, IdleHold},
+    stall::{StallAction, StallDetector},
+};
+use embassy_futures::select::{select, select4, Either, Either4};
+use embassy_time::{Duration as TickDuration, Instant, Timer, TICK_HZ};
+use esp32c6_hal::{i2c::I2C, peripherals::I2C0};
+
+use crate::klipper::angle::AngleQuery;
+use crate::klipper::settings;
+use crate::klipper::stepper::{
+    StepperCorrection, STEPPER_BUSY, STEPPER_CORRECTION, STEPPER_ENABLE_LEVEL, STEPPER_MOVE_QUEUE,
+    STEPPER_POSITION,
+};
+use crate::klipper::tmc_uart::{write_register, HOST_IHOLD_IRUN, LAST_HOST_TRANSFER};
+use crate::klipper::trsync::{TRSyncMessage, TRSYNC_ACTIVE, TRSYNC_CHANNEL};
+
+use super::autotune::autotune;
+use super::backlash::measure_backlash;
+use super::calibration::{calibrate, CalibrationError};
+use super::discovery::discover;
+use super::health::HealthReport;
+use super::homing::{refine_home, HomingError};
+use super::resonance::resonance_test;
+use super::{
+    AngleSensor, ClosedLoopConfig, EncoderMessage, EncoderSample, FilterConfig,
+    StallDetectionConfig, CALIBRATION_TABLE, CLOSED_LOOP_CONFIG, CURRENT_POLICY_CONFIG,
+    ENCODER_CHANNEL, ENCODER_ESTIMATE, ENCODER_GEOMETRY, ENCODER_HEALTH, ENCODER_LAST_SAMPLE,
+    ENCODER_SAMPLES, ENCODER_SAMPLE_INTERVAL, ESTIMATOR_GAINS, FOLLOWING_ERROR_STATS,
+    HEALTH_CHECK_CONFIG, HOME_REFINEMENT_CONFIG, IDLE_HOLD_CONFIG, MAGNET_SENSOR,
+    STALL_DETECTION_CONFIG, TRACKING_MAX_VELOCITY, TRIGGER_MAGNET_READ,
+};
+
+// Readings further apart than this come from separate moves, the estimate starts over at rest
+const ESTIMATOR_IDLE: TickDuration = TickDuration::from_millis(100);
+
+// The host reads IFCNT before and after each of its writes to check they went through, so stay off
+// the driver UART while the host is using it
+const TMC_HOST_QUIET: TickDuration = TickDuration::from_millis(100);
+
+// Time before reading again an encoder that didn't answer, when the reading can't be skipped
+const READ_RETRY: TickDuration = TickDuration::from_millis(1);
+
+#[embassy_executor::task]
+pub async fn as5600_task(driver: As5600<I2C<'static, I2C0>>) {
+    encoder_task(driver).await
+}
+
+pub async fn encoder_task<S: AngleSensor>(mut sensor: S) {
+    loop {
+        match sensor.status().await {
+            Ok(status) => match status.magnet {
+                MagnetState::Detected => {
+                    log::info!("Magnet detected");
+                    break;
+                }
+                MagnetState::Unknown => {
+                    log::info!("Sensor answering, magnet state unknown");
+                    break;
+                }
+                _ => {
+                    log::error!("Magnet not detected, or detected with error - {status:?}")
+                }
+            },
+            Err(e) => {
+                log::error!("Error with magnet detection occured : {:?}", e);
+            }
+        }
+    }
+    let mut geometry = ENCODER_GEOMETRY.lock(|unlocked| *unlocked.borrow());
+    check_resolution(&geometry, sensor.resolution());
+    let mut start_position = STEPPER_POSITION.lock(|unlocked| *unlocked.borrow());
+
+    let mut health_monitor = HealthMonitor::new(Default::default());
+    let mut tracker = MultiTurnTracker::new(geometry.resolution());
+    let mut start_ticks = resume_tracking(&mut sensor, &mut tracker, &mut health_monitor).await;
+    let mut last_sample = Instant::now();
+    let mut corrector = Corrector::new(Default::default());
+    let mut stall_detector = StallDetector::new(Default::default());
+    let mut estimator = Estimator::new(Default::default(), TICK_HZ);
+    let mut angle_query: Option<AngleQuery> = None;
+    let mut current_policy = CurrentPolicy::new(Default::default(), IholdIrun::from_bits(0));
+    let mut host_current: Option<(u8, IholdIrun)> = None;
+    let mut next_health_check = Instant::now();
+    let mut sample_interval: Option<u32> = None;
+    let mut next_reading = Instant::now();
+    let mut idle_hold = IdleHold::new(Default::default());
+    let mut next_idle_reading = Instant::now();
+    let mut homing_interval: Option<u32> = None;
+    let mut last_commanded = 0;
+    let mut correction_pending = false;
+    let samples = ENCODER_SAMPLES.immediate_publisher();
+
+    loop {
+        let configured_interval = ENCODER_SAMPLE_INTERVAL.lock(|unlocked| *unlocked.borrow());
+        if configured_interval != sample_interval {
+            sample_interval = configured_interval;
+            next_reading = Instant::now();
+        }
+
+        let next_angle_sample = angle_query
+            .as_ref()
+            .map_or(Instant::MAX, |query| query.next_sample());
+
+        let hold_config = IDLE_HOLD_CONFIG.lock(|unlocked| *unlocked.borrow());
+        // Readings that don't wait for steps, for the idle hold and angle endstops
+        let idle_interval = match (hold_config.map(|config| config.interval()), homing_interval) {
+            (Some(hold), Some(homing)) => Some(hold.min(homing)),
+            (hold, homing) => hold.or(homing),
+        };
+        // Fixed rate readings keep going while idle already
+        let idle_reading = idle_interval
+            .filter(|_| sample_interval.is_none())
+            .map(|_| next_idle_reading);
+
+        let health_check = HEALTH_CHECK_CONFIG.lock(|unlocked| *unlocked.borrow());
+        let next_health_check_at = if health_check.interval() == 0 {
+            Instant::MAX
+        } else {
+            next_health_check
+        };
+
+        match select4(
+            wait_reading(sample_interval.map(|_| next_reading), idle_reading),
+            ENCODER_CHANNEL.receive(),
+            Timer::at(next_angle_sample),
+            Timer::at(next_health_check_at),
+        )
+        .await
+        {
+            Either4::First(stepped) => {
+                TRIGGER_MAGNET_READ.reset();
+                if stepped {
+                    correction_pending = false;
+                }
+                if let Some(interval) = sample_interval {
+                    let interval = TickDuration::from_ticks(interval as u64);
+                    next_reading += interval;
+                    // Readings that fell behind are dropped rather than taken in a burst
+                    if next_reading < Instant::now() {
+                        log::warn!("Encoder reading overran its interval");
+                        next_reading = Instant::now() + interval;
+                    }
+                }
+                if let Some(interval) = idle_interval {
+                    next_idle_reading = Instant::now() + TickDuration::from_ticks(interval as u64);
+                }
+
+                let configured = ENCODER_GEOMETRY.lock(|unlocked| *unlocked.borrow());
+                if configured != geometry {
+                    log::info!("Encoder geometry changed to {:?}", configured);
+                    rebase_geometry(&geometry, &configured);
+                    geometry = configured;
+                    check_resolution(&geometry, sensor.resolution());
+                    // Start measuring from here again in the new units
+                    tracker = MultiTurnTracker::new(geometry.resolution());
+                    start_ticks =
+                        resume_tracking(&mut sensor, &mut tracker, &mut health_monitor).await;
+                    start_position = STEPPER_POSITION.lock(|unlocked| *unlocked.borrow());
+                    estimator.reset();
+                }
+
+                let sample = match sensor.angle().await {
+                    Ok(sample) => {
+                        report_health(health_monitor.update_readable(), None);
+                        sample
+                    }
+                    Err(e) => {
+                        // Skipped, it counts towards the health like a health check that failed
+                        log::warn!("Encoder could not be read : {:?}", e);
+                        report_health(health_monitor.update(None), None);
+                        continue;
+                    }
+                };
+                let angle = linearize(sample.angle);
+                let now = sample.timestamp;
+                let since_last_sample = now.duration_since(last_sample);
+                let elapsed = Duration::from_micros(since_last_sample.as_micros());
+                last_sample = now;
+
+                tracker.set_max_velocity(TRACKING_MAX_VELOCITY.lock(|unlocked| *unlocked.borrow()));
+                if let Err(e) = tracker.update(angle, elapsed) {
+                    log::warn!("Encoder turn count may be off : {:?}", e);
+                }
+                // Encoder ticks travelled since the task started
+                let encoder_ticks = tracker.position() - start_ticks;
+
+                if let Some(gains) = ESTIMATOR_GAINS.lock(|unlocked| *unlocked.borrow()) {
+                    estimator.set_gains(gains);
+                }
+                if since_last_sample > ESTIMATOR_IDLE {
+                    estimator.reset();
+                }
+                let estimate = estimator.update(encoder_ticks, now.as_ticks());
+                ENCODER_ESTIMATE.lock(|unlocked| {
+                    *unlocked.borrow_mut() = (now.as_ticks() as u32, estimate);
+                });
+
+                let commanded = STEPPER_POSITION
+                    .lock(|unlocked| *unlocked.borrow())
+                    .wrapping_sub(start_position);
+                let measured = geometry.ticks_to_steps(encoder_ticks) as i32;
+                let following_error = measured.wrapping_sub(commanded);
+
+                log::trace!(
+                    "Magnet sensor reading : {} | pos : {} | following error : {} steps | velocity : {} deg/s",
+                    encoder_ticks as f32 * geometry.degrees_per_tick(),
+                    commanded as f32 * geometry.degrees_per_step(),
+                    following_error,
+                    estimate.velocity * geometry.degrees_per_tick(),
+                );
+                MAGNET_SENSOR.signal(angle);
+                let sample = EncoderSample::new(
+                    now.as_ticks() as u32,
+                    angle,
+                    encoder_ticks,
+                    following_error,
+                );
+                ENCODER_LAST_SAMPLE.lock(|unlocked| *unlocked.borrow_mut() = Some(sample));
+                samples.publish_immediate(sample);
+                let commanded_moved = commanded != last_commanded;
+                let moving = stepped || commanded_moved;
+                last_commanded = commanded;
+                FOLLOWING_ERROR_STATS.lock(|unlocked| {
+                    unlocked.borrow_mut().record(following_error);
+                });
+                // Homing on the encoder runs into the end of the axis on purpose, there is no stall
+                // to act on and no error to correct until it is done
+                let homing = homing_interval.is_some();
+
+                let stall_config = STALL_DETECTION_CONFIG
+                    .lock(|unlocked| *unlocked.borrow())
+                    .filter(|_| !homing);
+                if let Some(config) = stall_config {
+                    stall_detector.set_config(config.stall());
+                    if let Some(action) = stall_detector.update(following_error, moving) {
+                        log::error!(
+                            "Stall detected with a following error of {following_error} steps"
+                        );
+                        match action {
+                            StallAction::Ignore => {}
+                            StallAction::Warn => {
+                                klipper_output!(
+                                    "[WARN] Encoder following error exceeded the stall threshold"
+                                );
+                            }
+                            StallAction::Trigger => {
+                                // Shoot up the flare, but only with a trsync around to see it. The
+                                // detector re-arms after every stall and waiting on a full channel
+                                // would hold up the whole task.
+                                let trigger = TRSyncMessage::NewTrigger {
+                                    reason: config.trigger_reason(),
+                                    trigger_time: Instant::now().as_ticks() as u32,
+                                };
+                                if !TRSYNC_ACTIVE.lock(|unlocked| *unlocked.borrow())
+                                    || TRSYNC_CHANNEL.try_send(trigger).is_err()
+                                {
+                                    klipper_output!(
+                                        "[WARN] Encoder stall with no trsync to trigger"
+                                    );
+                                }
+                            }
+                            StallAction::Shutdown => {
+                                klipper_shutdown!(
+                                    "Encoder detected a stall",
+                                    Instant::now().as_ticks() as u32
+                                );
+                            }
+                        }
+                    }
+                }
+
+                // A reading taken before the step driver went through the last correction doesn't
+                // show its effect yet
+                let closed_loop = CLOSED_LOOP_CONFIG
+                    .lock(|unlocked| *unlocked.borrow())
+                    .filter(|_| !correction_pending && !homing);
+                if let Some(config) = closed_loop {
+                    corrector.set_config(config.correction());
+                    let steps = corrector.update(following_error);
+                    if steps != 0 {
+                        correction_pending = true;
+                        log::debug!(
+                            "Correcting following error of {following_error} with {steps} steps"
+                        );
+                        // A newer correction replaces one that hasn't been picked up yet, it is
+                        // computed from a fresher reading of the same error
+                        STEPPER_CORRECTION
+                            .signal(StepperCorrection::new(steps, config.step_interval()));
+                    }
+                }
+
+                if let Some(config) = hold_config {
+                    idle_hold.set_config(config.hold());
+                    let enabled = STEPPER_ENABLE_LEVEL
+                        .lock(|unlocked| *unlocked.borrow())
+                        .map_or(true, |level| level == config.enable_level());
+                    // Steps the host commanded that are queued or going out are no disturbance
+                    let idle = enabled
+                        && !commanded_moved
+                        && !STEPPER_BUSY.lock(|unlocked| *unlocked.borrow())
+                        && STEPPER_MOVE_QUEUE.is_empty();
+                    match idle_hold.update(following_error, idle) {
+                        Some(HoldEvent::Disturbed(error)) => {
+                            log::warn!("Motor moved away by {error} steps while idle");
+                            klipper_reply!(
+                                encoder_idle_hold,
+                                oid: u8 = config.oid(),
+                                clock: u32 = now.as_ticks() as u32,
+                                disturbed: u8 = 1,
+                                following_error: i32 = error
+                            );
+                        }
+                        Some(HoldEvent::Settled) => {
+                            log::info!("Motor back at its commanded position");
+                            klipper_reply!(
+                                encoder_idle_hold,
+                                oid: u8 = config.oid(),
+                                clock: u32 = now.as_ticks() as u32,
+                                disturbed: u8 = 0,
+                                following_error: i32 = following_error
+                            );
+                        }
+                        None => {}
+                    }
+
+                    let steps = idle_hold.restore_steps(following_error);
+                    if steps != 0 && !correction_pending && !homing {
+                        correction_pending = true;
+                        log::debug!("Restoring idle position with {steps} steps");
+                        STEPPER_CORRECTION
+                            .signal(StepperCorrection::new(steps, config.step_interval()));
+                    }
+                }
+
+                if let Some(current) = HOST_IHOLD_IRUN.try_take() {
+                    // The host overwrote whatever we applied
+                    current_policy.reset();
+                    host_current = Some(current);
+                }
+                update_current(&mut current_policy, host_current, following_error);
+            }
+            Either4::Second(message) => match message {
+                EncoderMessage::Calibrate {
+                    oid,
+                    points,
+                    step_interval,
+                    settle_ticks,
+                } => match calibrate(&mut sensor, &geometry, points, step_interval, settle_ticks)
+                    .await
+                {
+                    Ok(table) => {
+                        log::info!(
+                            "Encoder calibration done, {} points with a max error of {} ticks",
+                            table.points(),
+                            table.max_error()
+                        );
+                        klipper_reply!(
+                            encoder_calibration_result,
+                            oid: u8 = oid,
+                            points: u16 = table.points(),
+                            max_error: u16 = table.max_error()
+                        );
+                        CALIBRATION_TABLE.lock(|unlocked| {
+                            *unlocked.borrow_mut() = Some(table);
+                        });
+                        // The moves took us back where we started, only the linearization of the
+                        // angle changed
+                        resume_tracking(&mut sensor, &mut tracker, &mut health_monitor).await;
+                        last_sample = Instant::now();
+                        estimator.reset();
+                    }
+                    Err(CalibrationError::Sensor(e)) => {
+                        log::error!("Encoder calibration failed to read the sensor : {:?}", e);
+                        klipper_output!("[ERROR] Encoder calibration failed to read the sensor");
+                    }
+                    Err(e) => {
+                        log::error!("Encoder calibration failed : {:?}", e);
+                        klipper_output!("[ERROR] Encoder calibration failed");
+                    }
+                },
+                EncoderMessage::Autotune {
+                    oid,
+                    steps,
+                    step_interval,
+                    settle_ticks,
+                    save,
+                } => match autotune(&mut sensor, &geometry, steps, step_interval, settle_ticks)
+                    .await
+                {
+                    Ok(tuning) => {
+                        log::info!("Encoder autotune done : {:?}", tuning);
+                        CLOSED_LOOP_CONFIG.lock(|unlocked| {
+                            *unlocked.borrow_mut() =
+                                Some(ClosedLoopConfig::new(oid, tuning.correction, step_interval));
+                        });
+                        // Stall detection keeps its action, it only gets a threshold to go with
+                        // the new correction
+                        STALL_DETECTION_CONFIG.lock(|unlocked| {
+                            let mut unlocked = unlocked.borrow_mut();
+                            if let Some(config) = unlocked.as_mut() {
+                                let mut stall = config.stall();
+                                stall.threshold = tuning.stall_threshold;
+                                *config =
+                                    StallDetectionConfig::new(oid, stall, config.trigger_reason());
+                            }
+                        });
+                        let saved = save && settings::save();
+                        klipper_reply!(
+                            encoder_autotune_result,
+                            oid: u8 = oid,
+                            band: u32 = tuning.correction.band,
+                            gain: u32 = (tuning.correction.gain * 1000.) as u32,
+                            max_steps: u32 = tuning.correction.max_steps,
+                            step_interval: u32 = step_interval,
+                            stall_threshold: u32 = tuning.stall_threshold,
+                            saved: u8 = saved as u8
+                        );
+                        last_sample = Instant::now();
+                        estimator.reset();
+                    }
+                    Err(e) => {
+                        log::error!("Encoder autotune failed : {:?}", e);
+                        klipper_output!("[ERROR] Encoder autotune failed");
+                    }
+                },
+                EncoderMessage::MeasureBacklash {
+                    oid,
+                    steps,
+                    cycles,
+                    step_interval,
+                    settle_ticks,
+                } => match measure_backlash(
+                    &mut sensor,
+                    &geometry,
+                    steps,
+                    cycles,
+                    step_interval,
+                    settle_ticks,
+                )
+                .await
+                {
+                    Ok(backlash) => {
+                        // Measured in thousandths of a step
+                        let lost = backlash.backlash().unwrap_or(0.);
+                        log::info!(
+                            "Backlash of {} steps, {} degrees",
+                            lost / 1000.,
+                            lost * geometry.degrees_per_step() / 1000.
+                        );
+                        klipper_reply!(
+                            encoder_backlash_result,
+                            oid: u8 = oid,
+                            backlash: i32 = lost as i32,
+                            forward: i32 = backlash.forward().unwrap_or(0.) as i32,
+                            backward: i32 = backlash.backward().unwrap_or(0.) as i32,
+                            degrees: i32 = (lost * geometry.degrees_per_step()) as i32
+                        );
+                        // The moves were too short to lose track of the revolution, but the
+                        // estimate was not fed while they ran
+                        last_sample = Instant::now();
+                        estimator.reset();
+                    }
+                    Err(e) => {
+                        log::error!("Backlash measurement failed : {:?}", e);
+                        klipper_output!("[ERROR] Backlash measurement failed");
+                    }
+                },
+                EncoderMessage::Discover {
+                    oid,
+                    steps,
+                    microsteps,
+                    step_interval,
+                    settle_ticks,
+                    save,
+                } => match discover(&mut sensor, steps, microsteps, step_interval, settle_ticks)
+                    .await
+                {
+                    Ok(discovered) => {
+                        log::info!("Encoder geometry discovered : {:?}", discovered);
+                        // Picked up and rebased on the next reading, like a configured geometry
+                        ENCODER_GEOMETRY.lock(|unlocked| *unlocked.borrow_mut() = discovered);
+                        let saved = save && settings::save();
+                        klipper_reply!(
+                            encoder_discovery_result,
+                            oid: u8 = oid,
+                            inverted: u8 = discovered.inverted() as u8,
+                            steps_per_rev: u32 = discovered.steps_per_rev(),
+                            full_steps: u16 = discovered.full_steps(),
+                            microsteps: u16 = discovered.microsteps(),
+                            saved: u8 = saved as u8
+                        );
+                        last_sample = Instant::now();
+                        estimator.reset();
+                    }
+                    Err(e) => {
+                        log::error!("Encoder discovery failed : {:?}", e);
+                        klipper_output!(
+                            "[ERROR] Encoder discovery failed, check the wiring and microsteps"
+                        );
+                    }
+                },
+                EncoderMessage::ResonanceTest {
+                    oid,
+                    steps,
+                    freq_start,
+                    freq_end,
+                    samples,
+                    sample_ticks,
+                } => match resonance_test(
+                    &mut sensor,
+                    &geometry,
+                    steps,
+                    freq_start,
+                    freq_end,
+                    samples,
+                    sample_ticks,
+                )
+                .await
+                {
+                    Ok(resonance) => {
+                        for (rank, peak) in resonance.peaks[..resonance.found].iter().enumerate() {
+                            log::info!(
+                                "Resonance at {} Hz, {} steps",
+                                peak.frequency,
+                                peak.amplitude
+                            );
+                            klipper_reply!(
+                                encoder_resonance_peak,
+                                oid: u8 = oid,
+                                rank: u8 = rank as u8,
+                                frequency: u32 = (peak.frequency * 1000.) as u32,
+                                amplitude: u32 = (peak.amplitude * 1000.) as u32
+                            );
+                        }
+                        klipper_reply!(
+                            encoder_resonance_result,
+                            oid: u8 = oid,
+                            samples: u16 = samples,
+                            bin_width: u32 = (resonance.bin_width * 1000.) as u32,
+                            peaks: u8 = resonance.found as u8
+                        );
+                        // The sweep was too short to lose track of the revolution, but the estimate
+                        // was not fed while it ran
+                        last_sample = Instant::now();
+                        estimator.reset();
+                    }
+                    Err(e) => {
+                        log::error!("Resonance test failed : {:?}", e);
+                        klipper_output!("[ERROR] Resonance test failed");
+                    }
+                },
+                EncoderMessage::RefineHome => {
+                    let config = HOME_REFINEMENT_CONFIG.lock(|unlocked| *unlocked.borrow());
+                    if let Some(config) = config {
+                        match refine_home(&mut sensor, &geometry, &config).await {
+                            Ok(refinement) => {
+                                log::info!(
+                                    "Home refined by {} steps, {} steps off the target",
+                                    refinement.steps,
+                                    refinement.error
+                                );
+                                klipper_reply!(
+                                    encoder_home_refined,
+                                    oid: u8 = config.oid(),
+                                    steps: i32 = refinement.steps,
+                                    error: i32 = refinement.error
+                                );
+                            }
+                            Err(HomingError::NotStopped) => {
+                                log::error!("Stepper still moving after the endstop triggered");
+                                klipper_output!(
+                                    "[ERROR] Homing failed, the stepper did not stop on the trigger"
+                                );
+                            }
+                            Err(e) => {
+                                log::error!("Home refinement failed : {:?}", e);
+                                klipper_output!("[ERROR] Home refinement failed");
+                            }
+                        }
+                        // Homing ends here, the following error is measured from this position on
+                        start_ticks =
+                            resume_tracking(&mut sensor, &mut tracker, &mut health_monitor).await;
+                        start_position = STEPPER_POSITION.lock(|unlocked| *unlocked.borrow());
+                        last_sample = Instant::now();
+                        estimator.reset();
+                    }
+                }
+                EncoderMessage::Homing { interval } => {
+                    if homing_interval.is_some() && interval.is_none() {
+                        // Whatever the end of the axis left of the following error is no drift,
+                        // measure from here on before stall detection and correction pick up again
+                        start_ticks =
+                            resume_tracking(&mut sensor, &mut tracker, &mut health_monitor).await;
+                        start_position = STEPPER_POSITION.lock(|unlocked| *unlocked.borrow());
+                        last_sample = Instant::now();
+                        estimator.reset();
+                    }
+                    stall_detector.reset();
+                    homing_interval = interval;
+                    next_idle_reading = Instant::now();
+                }
+                EncoderMessage::Filter { oid, config } => {
+                    update_filter(&mut sensor, oid, config).await;
+                }
+                EncoderMessage::QueryAngle { query } => {
+                    // Flush what the previous stream collected before replacing it
+                    if let Some(previous) = angle_query.take() {
+                        previous.finish();
+                    }
+                    angle_query = query;
+                }
+            },
+            Either4::Third(_) => {
+                if let Some(query) = angle_query.as_mut() {
+                    // The host runs its own calibration, so it gets the raw angle
+                    match sensor.raw_angle().await {
+                        Ok(sample) => query.add_sample(
+                            sample.timestamp,
+                            Some(sample.angle),
+                            sensor.resolution(),
+                        ),
+                        Err(_) => query.add_sample(Instant::now(), None, sensor.resolution()),
+                    }
+                }
+            }
+            Either4::Fourth(_) => {
+                let now = Instant::now();
+                next_health_check = now + TickDuration::from_ticks(health_check.interval() as u64);
+
+                health_monitor.set_limits(health_check.limits());
+                let sample = sensor.status().await.ok();
+                let event = health_monitor.update(sample);
+                ENCODER_HEALTH.lock(|unlocked| {
+                    *unlocked.borrow_mut() =
+                        HealthReport::new(now.as_ticks() as u32, sample, health_monitor.fault());
+                });
+                report_health(event, sample);
+            }
+        }
+    }
+}
+
+// Picks up the angle closest to the tracker's position again and returns the position. Tracking
+// can't go on from a guess, so a read that failed is retried.
+async fn resume_tracking<S: AngleSensor>(
+    sensor: &mut S,
+    tracker: &mut MultiTurnTracker,
+    health_monitor: &mut HealthMonitor,
+) -> i64 {
+    tracker.reset();
+    loop {
+        match sensor.angle().await {
+            Ok(sample) => {
+                report_health(health_monitor.update_readable(), None);
+                return tracker
+                    .update(linearize(sample.angle), Duration::ZERO)
+                    .unwrap();
+            }
+            Err(e) => {
+                log::warn!("Encoder could not be read : {:?}", e);
+                report_health(health_monitor.update(None), None);
+                Timer::after(READ_RETRY).await;
+            }
+        }
+    }
+}
+
+fn report_health(event: Option<HealthEvent>, sample: Option<HealthSample>) {
+    match event {
+        Some(HealthEvent::Warning(fault)) => {
+            log::warn!("Encoder health warning : {:?} - {:?}", fault, sample);
+            warn_fault(fault);
+        }
+        Some(HealthEvent::Shutdown(fault)) => {
+            log::error!("Encoder health failure : {:?} - {:?}", fault, sample);
+            klipper_shutdown!(
+                "Encoder magnet lost or out of range",
+                Instant::now().as_ticks() as u32
+            );
+        }
+        Some(HealthEvent::Recovered) => {
+            log::info!("Encoder health recovered - {:?}", sample);
+            klipper_output!("[INFO] Encoder magnet back within range");
+        }
+        None => {}
+    }
+}
+
+// Waits for the next scheduled reading, or for the step driver to finish a step group or `idle`,
+// whichever comes first. Returns whether the step driver went through steps since the last reading.
+async fn wait_reading(scheduled: Option<Instant>, idle: Option<Instant>) -> bool {
+    match scheduled {
+        Some(at) => {
+            Timer::at(at).await;
+            TRIGGER_MAGNET_READ.signaled()
+        }
+        None => match select(
+            TRIGGER_MAGNET_READ.wait(),
+            Timer::at(idle.unwrap_or(Instant::MAX)),
+        )
+        .await
+        {
+            Either::First(_) => true,
+            Either::Second(_) => false,
+        },
+    }
+}
+
+// Applies `config` if given, then reports the filter settings in use
+async fn update_filter<S: AngleSensor>(sensor: &mut S, oid: u8, config: Option<FilterConfig>) {
+    if let Some(config) = config {
+        match sensor.set_filter_config(config).await {
+            Ok(true) => log::info!("Encoder filter set to {:?}", config),
+            Ok(false) => {
+                klipper_output!("[ERROR] Encoder sensor has no filter settings");
+                return;
+            }
+            Err(e) => {
+                log::error!("Encoder filter could not be set to {:?} : {:?}", config, e);
+                klipper_output!("[ERROR] Encoder filter settings rejected");
+            }
+        }
+    }
+
+    match sensor.filter_config().await {
+        Ok(Some(config)) => {
+            klipper_reply!(
+                encoder_filter,
+                oid: u8 = oid,
+                slow_filter: u8 = config.slow_filter,
+                fast_filter_threshold: u8 = config.fast_filter_threshold,
+                hysteresis: u8 = config.hysteresis,
+                power_mode: u8 = config.power_mode
+            );
+        }
+        Ok(None) => klipper_output!("[ERROR] Encoder sensor has no filter settings"),
+        Err(e) => {
+            log::error!("Encoder filter could not be read : {:?}", e);
+            klipper_output!("[ERROR] Encoder filter could not be read");
+        }
+    }
+}
+
+fn warn_fault(fault: Fault) {
+    match fault {
+        Fault::Unreadable => klipper_output!("[WARN] Encoder could not be read"),
+        Fault::MagnetMissing => klipper_output!("[WARN] Encoder magnet not detected"),
+        Fault::MagnetTooWeak => klipper_output!("[WARN] Encoder magnet too weak"),
+        Fault::MagnetTooStrong => klipper_output!("[WARN] Encoder magnet too strong"),
+        Fault::GainSaturated => klipper_output!("[WARN] Encoder gain control saturated"),
+        Fault::Magnitude => klipper_output!("[WARN] Encoder field magnitude out of range"),
+    }
+}
+
+// Applies the current policy on the driver whose current the host configured last
+fn update_current(
+    policy: &mut CurrentPolicy,
+    host_current: Option<(u8, IholdIrun)>,
+    following_error: i32,
+) {
+    let Some((slave, baseline)) = host_current else {
+        return;
+    };
+    let last_host_transfer = LAST_HOST_TRANSFER.lock(|unlocked| *unlocked.borrow());
+    if Instant::now().duration_since(last_host_transfer) < TMC_HOST_QUIET {
+        return;
+    }
+
+    let config = CURRENT_POLICY_CONFIG
+        .lock(|unlocked| *unlocked.borrow())
+        .filter(|config| config.slave() == slave);
+    let Some(config) = config else {
+        // Hand the current back to the host
+        if policy.applied().is_some() && write_register(slave, IHOLD_IRUN, baseline.to_bits()) {
+            policy.reset();
+        }
+        return;
+    };
+
+    policy.set_config(config.current());
+    policy.set_baseline(baseline);
+    if let Some(current) = policy.update(following_error) {
+        log::debug!(
+            "Driver current set to {:?} for a following error of {following_error}",
+            current
+        );
+        if !write_register(slave, IHOLD_IRUN, current.to_bits()) {
+            log::warn!("Driver current could not be written");
+            policy.reset();
+        }
+    }
+}
+
+// Angles are tracked at the geometry resolution, which only works out if it is the sensor's
+fn check_resolution(geometry: &EncoderGeometry, sensor_resolution: u16) {
+    if geometry.resolution() != sensor_resolution {
+        log::warn!(
+            "Encoder geometry resolution of {} does not match the sensor's {}",
+            geometry.resolution(),
+            sensor_resolution
+        );
+        klipper_output!("[WARN] Encoder resolution does not match the angle sensor");
+    }
+}
+
+// A table recorded at another resolution no longer matches the encoder readings
+fn rebase_geometry(previous: &EncoderGeometry, configured: &EncoderGeometry) {
+    if previous.resolution() != configured.resolution() {
+        CALIBRATION_TABLE.lock(|unlocked| {
+            if unlocked.borrow_mut().take().is_some() {
+                log::warn!("Encoder resolution changed, dropping the calibration table");
+            }
+        });
+    }
+}
+
+pub(super) fn linearize(angle: u16) -> u16 {
+    CALIBRATION_TABLE.lock(|unlocked| match unlocked.borrow().as_ref() {
+        Some(table) => table.correct(angle),
+        None => angle,
+    })
+}
@@ -1,8 +1,10 @@
 use anchor::*;
+use closed_loop::window::AngleWindow;
 use heapless::Entry;
 
 use embassy_time::Instant;
 
+use crate::klipper::encoder::ENCODER_GEOMETRY;
 use crate::klipper::oid_types::*;
 
 mod endstop_pin;
@@ -13,7 +15,7 @@ mod task;
 use endstop_pin::EndstopPin;
 pub use global::*;
 use message::EndstopMessage;
-use task::endstop_runner;
+use task::{angle_endstop_runner, endstop_runner, following_error_endstop_runner};
 
 #[klipper_command]
 pub fn endstop_home(
@@ -71,7 +73,30 @@ pub fn endstop_home(
                 ))
                 .unwrap();
         }
-        _ => panic!("Expected OID to be a Endstop or EndstopPullup, but it wasn't!"),
+        OIDTypes::AngleEndstop { _inner } => {
+            context
+                .spawner
+                .spawn(angle_endstop_runner(
+                    rest_ticks,
+                    pin_value,
+                    trigger_reason,
+                    _inner.window(),
+                ))
+                .unwrap();
+        }
+        OIDTypes::FollowingErrorEndstop { _inner } => {
+            context
+                .spawner
+                .spawn(following_error_endstop_runner(
+                    sample_count,
+                    rest_ticks,
+                    pin_value,
+                    trigger_reason,
+                    _inner.threshold(),
+                ))
+                .unwrap();
+        }
+        _ => panic!("Expected OID to be an endstop, but it wasn't!"),
     };
 }
 
@@ -79,27 +104,33 @@ pub fn endstop_home(
 pub fn endstop_query_state(context: &mut crate::State, oid: u8) {
     log::trace!("[ANCHOR] Endstop Query State - OID : {oid}");
 
-    match context.oids.get(&oid).unwrap() {
-        OIDTypes::Endstop { _inner } => {
-            log::trace!(
-                "Sending endstop_state : {oid}, {}, {}, {}",
-                _inner.is_homing(),
-                _inner.next_clock(),
-                _inner.get_pin_val()
-            );
-            klipper_reply!(endstop_state, oid: u8 = oid, homing: u8 = _inner.is_homing() as u8, next_clock:u32 = Instant::now().as_ticks() as u32, pin_value: u8 = _inner.get_pin_val() as u8)
-        }
-        OIDTypes::EndstopPullup { _inner } => {
-            log::trace!(
-                "Sending endstop_state : {oid}, {}, {}, {}",
-                _inner.is_homing(),
-                _inner.next_clock(),
-                _inner.get_pin_val()
-            );
-            klipper_reply!(endstop_state, oid: u8 = oid, homing: u8 = _inner.is_homing() as u8, next_clock:u32 = Instant::now().as_ticks() as u32, pin_value: u8 = _inner.get_pin_val() as u8)
-        }
-        _ => panic!("Expected OID to be a Endstop or EndstopPullup, but it wasn't!"),
-    }
+    // Every kind of endstop answers the same way
+    let (homing, next_clock, pin_value) = match context.oids.get(&oid).unwrap() {
+        OIDTypes::Endstop { _inner } => (
+            _inner.is_homing(),
+            _inner.next_clock(),
+            _inner.get_pin_val(),
+        ),
+        OIDTypes::EndstopPullup { _inner } => (
+            _inner.is_homing(),
+            _inner.next_clock(),
+            _inner.get_pin_val(),
+        ),
+        OIDTypes::AngleEndstop { _inner } => (
+            _inner.is_homing(),
+            _inner.next_clock(),
+            _inner.get_pin_val(),
+        ),
+        OIDTypes::FollowingErrorEndstop { _inner } => (
+            _inner.is_homing(),
+            _inner.next_clock(),
+            _inner.get_pin_val(),
+        ),
+        _ => panic!("Expected OID to be an endstop, but it wasn't!"),
+    };
+
+    log::trace!("Sending endstop_state : {oid}, {homing}, {next_clock}, {pin_value}");
+    klipper_reply!(endstop_state, oid: u8 = oid, homing: u8 = homing as u8, next_clock:u32 = Instant::now().as_ticks() as u32, pin_value: u8 = pin_value as u8)
 }
 
 #[klipper_command]
@@ -144,3 +175,68 @@ pub fn config_endstop(context: &mut crate::State, oid: u8, pin: u8, pull_up: u8)
         }
     }
 }
+
+/// Configures an endstop without a switch that homes on the encoder angle, its pin is high while
+/// the linearized angle is between `start` and `end` ticks, through 0 when `end` is below `start`.
+/// A `start` equal to `end` is a threshold, crossing it counts as entering the window. It is homed
+/// with `endstop_home` like any other endstop, the encoder is read every `rest_ticks` meanwhile.
+#[klipper_command]
+pub fn config_angle_endstop(context: &mut crate::State, oid: u8, start: u16, end: u16) {
+    log::trace!("[ANCHOR] Config Angle Endstop - oid: {oid}, start: {start}, end: {end}");
+
+    let resolution = ENCODER_GEOMETRY.lock(|unlocked| unlocked.borrow().resolution());
+    let window = match AngleWindow::new(start, end, resolution) {
+        Ok(window) => window,
+        Err(e) => {
+            log::error!("Invalid angle endstop window : {:?}", e);
+            klipper_output!("[ERROR] Invalid angle endstop window");
+            return;
+        }
+    };
+
+    match context.oids.entry(oid) {
+        Entry::Occupied(mut o) => {
+            log::trace!("[ANCHOR] Reconfiguring configured OID to Angle Endstop Entry");
+            let oid_entry = o.get_mut();
+            *oid_entry = OIDTypes::AngleEndstop {
+                _inner: AngleEndstop::new(window),
+            }
+        }
+        Entry::Vacant(v) => {
+            let _ = v.insert(OIDTypes::AngleEndstop {
+                _inner: AngleEndstop::new(window),
+            });
+        }
+    }
+}
+
+/// Configures an endstop without a switch for sensorless homing, it triggers once the encoder
+/// following error grew by more than `threshold` steps since homing started, the carriage ran into
+/// the end of the axis. `endstop_home` takes `sample_count` readings above the threshold in a row,
+/// the encoder is read every `rest_ticks` meanwhile. The threshold has to stay above the lag of
+/// the motor accelerating into the homing move. Stall detection and closed-loop correction are
+/// paused while it homes, the following error is measured from where the motor stopped afterwards.
+#[klipper_command]
+pub fn config_following_error_endstop(context: &mut crate::State, oid: u8, threshold: u32) {
+    log::trace!("[ANCHOR] Config Following Error Endstop - oid: {oid}, threshold: {threshold}");
+
+    if threshold == 0 {
+        klipper_output!("[ERROR] Following error endstop needs a threshold");
+        return;
+    }
+
+    match context.oids.entry(oid) {
+        Entry::Occupied(mut o) => {
+            log::trace!("[ANCHOR] Reconfiguring configured OID to Following Error Endstop Entry");
+            let oid_entry = o.get_mut();
+            *oid_entry = OIDTypes::FollowingErrorEndstop {
+                _inner: FollowingErrorEndstop::new(threshold),
+            }
+        }
+        Entry::Vacant(v) => {
+            let _ = v.insert(OIDTypes::FollowingErrorEndstop {
+                _inner: FollowingErrorEndstop::new(threshold),
+            });
+        }
+    }
+}
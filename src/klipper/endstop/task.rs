@@ -1,7 +1,15 @@
-use embassy_futures::select::Either;
+use anchor::*;
+use closed_loop::{
+    stall::{StallAction, StallConfig, StallDetector},
+    window::AngleWindow,
+};
+use embassy_futures::select::{select, Either};
 
 use embassy_time::Instant;
 
+use crate::klipper::encoder::{
+    EncoderMessage, EncoderSample, ENCODER_CHANNEL, ENCODER_SAMPLES, HOME_REFINEMENT_CONFIG,
+};
 use crate::klipper::trsync::{TRSyncMessage, TRSYNC_CHANNEL};
 
 use super::EndstopPin;
@@ -72,6 +80,8 @@ pub async fn endstop_runner(
             }
 
             true => {
+                refine_home().await;
+
                 // The only message we can get is that we should die since the Trsync timed out
                 // We should probably match here to be better
                 log::debug!("Killing homer");
@@ -81,3 +91,116 @@ pub async fn endstop_runner(
         }
     }
 }
+
+// Homes on the encoder angle instead of a pin, the pin is high while the angle is within `window`.
+// A window passed between two readings counts as entered so a threshold or a narrow window isn't
+// missed at speed.
+#[embassy_executor::task]
+pub async fn angle_endstop_runner(
+    rest_ticks: u32,
+    pin_value: u8,
+    trigger_reason: u8,
+    window: AngleWindow,
+) {
+    let mut last_angle = None;
+    home_on_encoder(rest_ticks, trigger_reason, |sample| {
+        let angle = sample.angle();
+        let reached = match last_angle {
+            Some(last) => window.reached(last, angle, pin_value == 1),
+            None => window.contains(angle) == (pin_value == 1),
+        };
+        last_angle = Some(angle);
+        reached
+    })
+    .await;
+}
+
+// Sensorless homing, the pin is high once the following error grew by more than `threshold` steps
+// since homing started for `sample_count` readings in a row, the carriage ran into the end of the
+// axis. The error is taken from the start of homing so an offset left from before doesn't count.
+#[embassy_executor::task]
+pub async fn following_error_endstop_runner(
+    sample_count: u8,
+    rest_ticks: u32,
+    pin_value: u8,
+    trigger_reason: u8,
+    threshold: u32,
+) {
+    let mut detector = StallDetector::new(StallConfig {
+        threshold,
+        samples: sample_count,
+        action: StallAction::Trigger,
+    });
+    let mut start_error = None;
+    home_on_encoder(rest_ticks, trigger_reason, |sample| {
+        let start_error = *start_error.get_or_insert(sample.following_error());
+        let following_error = sample.following_error().wrapping_sub(start_error);
+        if pin_value == 1 {
+            detector.update(following_error, true).is_some()
+        } else {
+            following_error.unsigned_abs() <= threshold
+        }
+    })
+    .await;
+}
+
+// Reads the encoder every `rest_ticks` while homing and triggers at the first reading `reached`
+// accepts, like a pin would for `endstop_runner`
+async fn home_on_encoder(
+    rest_ticks: u32,
+    trigger_reason: u8,
+    mut reached: impl FnMut(&EncoderSample) -> bool,
+) {
+    // Every encoder endstop homing at once holds a subscriber, there are only so many of them
+    let Ok(mut samples) = ENCODER_SAMPLES.subscriber() else {
+        klipper_shutdown!(
+            "Too many endstops homing on the encoder",
+            Instant::now().as_ticks() as u32
+        );
+        return;
+    };
+    ENCODER_CHANNEL
+        .send(EncoderMessage::Homing {
+            interval: Some(rest_ticks),
+        })
+        .await;
+
+    loop {
+        match select(samples.next_message_pure(), ENDSTOP_CHANNEL.receive()).await {
+            Either::First(sample) => {
+                if reached(&sample) {
+                    // Shoot up the flare
+                    TRSYNC_CHANNEL
+                        .send(TRSyncMessage::NewTrigger {
+                            reason: trigger_reason,
+                            trigger_time: sample.clock(),
+                        })
+                        .await;
+                    break;
+                }
+            }
+            Either::Second(_) => {
+                // The Trsync timed out before we got there
+                ENCODER_CHANNEL
+                    .send(EncoderMessage::Homing { interval: None })
+                    .await;
+                return;
+            }
+        }
+    }
+
+    ENCODER_CHANNEL
+        .send(EncoderMessage::Homing { interval: None })
+        .await;
+    refine_home().await;
+
+    log::debug!("Killing encoder homer");
+    let _ = ENDSTOP_CHANNEL.receive().await;
+}
+
+// The encoder settles the motor on its angle once the trigger stopped it
+async fn refine_home() {
+    if HOME_REFINEMENT_CONFIG.lock(|unlocked| unlocked.borrow().is_some()) {
+        ENCODER_CHANNEL.send(EncoderMessage::RefineHome).await;
+    }
+}
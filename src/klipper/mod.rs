@@ -8,9 +8,12 @@ use embassy_sync::signal::Signal;
 use embassy_time::Instant;
 
 // pub mod commands;
+pub mod angle;
 pub mod digital_out;
+pub mod encoder;
 pub mod endstop;
 pub mod oid_types;
+pub mod settings;
 pub mod stepper;
 pub mod tmc_uart;
 pub mod trsync;
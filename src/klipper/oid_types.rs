@@ -1,8 +1,11 @@
+use closed_loop::window::AngleWindow;
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Sender, signal::Signal};
 use embassy_time::Instant;
 use embedded_io::{Read, Write};
 use esp32c6_hal::{gpio::InputPin, peripheral::Peripheral};
 
+use super::encoder::ENCODER_LAST_SAMPLE;
+use super::tmc_uart::{with_serial, TMCSerial};
 use super::trsync::TRSYNC_CHANNEL;
 use crate::klipper::stepper::StepInfo;
 
@@ -14,35 +17,43 @@ pub enum OIDTypes<'a> {
     DigitalOut { _inner: DigitalOut },
     Endstop { _inner: Endstop },
     EndstopPullup { _inner: EndstopPullup },
+    AngleEndstop { _inner: AngleEndstop },
+    FollowingErrorEndstop { _inner: FollowingErrorEndstop },
     TRSync { _inner: TRSync },
+    Angle { _inner: Angle },
+    Spi { _inner: Spi },
 }
 
 pub struct TMCUart<'a> {
     pull_up: bool,
-    uart: esp32c6_hal::uart::Uart<'a, esp32c6_hal::peripherals::UART1>,
+    serial: &'a TMCSerial,
     bit_time: u32,
 }
 
 impl<'a> TMCUart<'a> {
-    pub fn new(
-        uart: esp32c6_hal::Uart<'a, esp32c6_hal::peripherals::UART1>,
-        pull_up: bool,
-        bit_time: u32,
-    ) -> Self {
+    pub fn new(serial: &'a TMCSerial, pull_up: bool, bit_time: u32) -> Self {
         Self {
-            uart,
+            serial,
             pull_up,
             bit_time,
         }
     }
 
-    pub fn send(&mut self, buf: &[u8]) -> Result<usize, esp32c6_hal::uart::Error> {
-        self.uart.write(buf)
-    }
-
-    pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize, esp32c6_hal::uart::Error> {
-        self.uart.read_exact(buf).unwrap();
-        Ok(buf.len())
+    // Sends `buf` and reads `response.len()` bytes of reply after it, `None` while the MCU is
+    // writing a register of its own
+    pub fn transfer(
+        &mut self,
+        buf: &[u8],
+        response: &mut [u8],
+    ) -> Option<Result<usize, esp32c6_hal::uart::Error>> {
+        with_serial(self.serial, |uart| {
+            uart.write(buf)?;
+            // Burn our own sent bytes from the buffer, we don't want those.
+            let mut echo = [0; 8];
+            uart.read_exact(&mut echo[..buf.len()]).unwrap();
+            uart.read_exact(response).unwrap();
+            Ok(response.len())
+        })
     }
 }
 
@@ -190,6 +201,79 @@ impl Endstop {
     }
 }
 
+// Endstop without a switch, its pin is high while the encoder angle is within the window
+pub struct AngleEndstop {
+    window: AngleWindow,
+    homing: bool,
+    clock: Instant,
+}
+
+impl AngleEndstop {
+    pub fn new(window: AngleWindow) -> Self {
+        Self {
+            window,
+            homing: false,
+            clock: Instant::from_ticks(0),
+        }
+    }
+
+    pub fn window(&self) -> AngleWindow {
+        self.window
+    }
+
+    // Low until the encoder has been read
+    pub fn get_pin_val(&self) -> bool {
+        ENCODER_LAST_SAMPLE
+            .lock(|unlocked| *unlocked.borrow())
+            .is_some_and(|sample| self.window.contains(sample.angle()))
+    }
+
+    pub fn is_homing(&self) -> bool {
+        self.homing
+    }
+
+    pub fn next_clock(&self) -> u32 {
+        self.clock.as_ticks() as u32
+    }
+}
+
+// Endstop for sensorless homing, its pin is high while the following error is above the threshold
+pub struct FollowingErrorEndstop {
+    threshold: u32,
+    homing: bool,
+    clock: Instant,
+}
+
+impl FollowingErrorEndstop {
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            homing: false,
+            clock: Instant::from_ticks(0),
+        }
+    }
+
+    // Following error, in steps, of the carriage hitting the end of the axis
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    // Low until the encoder has been read
+    pub fn get_pin_val(&self) -> bool {
+        ENCODER_LAST_SAMPLE
+            .lock(|unlocked| *unlocked.borrow())
+            .is_some_and(|sample| sample.following_error().unsigned_abs() > self.threshold)
+    }
+
+    pub fn is_homing(&self) -> bool {
+        self.homing
+    }
+
+    pub fn next_clock(&self) -> u32 {
+        self.clock.as_ticks() as u32
+    }
+}
+
 pub struct TRSync {
     triggering_signal: Option<&'static Signal<CriticalSectionRawMutex, bool>>,
     signal_to_alert: Option<&'static Signal<CriticalSectionRawMutex, bool>>,
@@ -217,3 +301,19 @@ impl TRSync {
         embassy_futures::block_on(TRSYNC_CHANNEL.send(data));
     }
 }
+
+pub struct Angle {
+    angle_type: u8,
+}
+
+impl Angle {
+    pub fn new(angle_type: u8) -> Self {
+        Self { angle_type }
+    }
+
+    pub fn angle_type(&self) -> u8 {
+        self.angle_type
+    }
+}
+
+pub struct Spi;
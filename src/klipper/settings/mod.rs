@@ -0,0 +1,153 @@
+use anchor::*;
+use closed_loop::settings::{CorrectionSettings, HealthSettings, Settings, StallSettings};
+
+use crate::klipper::encoder::{
+    ClosedLoopConfig, HealthCheckConfig, StallDetectionConfig, CALIBRATION_TABLE,
+    CLOSED_LOOP_CONFIG, ENCODER_GEOMETRY, ESTIMATOR_GAINS, HEALTH_CHECK_CONFIG,
+    STALL_DETECTION_CONFIG, TRACKING_MAX_VELOCITY,
+};
+use crate::klipper::oid_types::*;
+
+mod storage;
+
+/// Stores the encoder setup and calibration of the stepper in flash
+#[klipper_command]
+pub fn save_encoder_settings(context: &mut crate::State, oid: u8) {
+    log::trace!("[ANCHOR] Save Encoder Settings - oid: {oid}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let success = save();
+            klipper_reply!(encoder_settings_result, oid: u8 = oid, success: u8 = success as u8);
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Restores everything stored by `save_encoder_settings`, including the closed loop correction and
+/// stall detection which are not restored at boot
+#[klipper_command]
+pub fn load_encoder_settings(context: &mut crate::State, oid: u8) {
+    log::trace!("[ANCHOR] Load Encoder Settings - oid: {oid}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let success = match storage::read() {
+                Ok(settings) => {
+                    apply(&settings, Some(oid));
+                    true
+                }
+                Err(e) => {
+                    log::error!("Loading encoder settings failed : {:?}", e);
+                    klipper_output!("[ERROR] Loading encoder settings failed");
+                    false
+                }
+            };
+            klipper_reply!(encoder_settings_result, oid: u8 = oid, success: u8 = success as u8);
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+/// Erases the stored settings, the settings currently in use are kept until the next reset
+#[klipper_command]
+pub fn erase_encoder_settings(context: &mut crate::State, oid: u8) {
+    log::trace!("[ANCHOR] Erase Encoder Settings - oid: {oid}");
+
+    match context.oids.get(&oid).unwrap() {
+        OIDTypes::Stepper { _inner } => {
+            let success = match storage::erase() {
+                Ok(()) => true,
+                Err(e) => {
+                    log::error!("Erasing encoder settings failed : {:?}", e);
+                    klipper_output!("[ERROR] Erasing encoder settings failed");
+                    false
+                }
+            };
+            klipper_reply!(encoder_settings_result, oid: u8 = oid, success: u8 = success as u8);
+        }
+        _ => panic!("Expected OIDType::Stepper, but it is something else!"),
+    }
+}
+
+// Stores the encoder setup currently in use, returns whether it went through
+pub fn save() -> bool {
+    match storage::write(&snapshot()) {
+        Ok(length) => {
+            log::info!("Saved {length} bytes of encoder settings");
+            true
+        }
+        Err(e) => {
+            log::error!("Saving encoder settings failed : {:?}", e);
+            klipper_output!("[ERROR] Saving encoder settings failed");
+            false
+        }
+    }
+}
+
+// Applies the stored encoder setup before the host connects. Correction and stall detection act
+// on a stepper, so they wait for `load_encoder_settings`.
+pub fn restore() {
+    match storage::read() {
+        Ok(settings) => {
+            log::info!("Restoring encoder settings");
+            apply(&settings, None);
+        }
+        Err(e) => log::info!("No encoder settings restored : {:?}", e),
+    }
+}
+
+fn snapshot() -> Settings {
+    Settings {
+        geometry: ENCODER_GEOMETRY.lock(|unlocked| *unlocked.borrow()),
+        calibration: CALIBRATION_TABLE.lock(|unlocked| unlocked.borrow().clone()),
+        correction: CLOSED_LOOP_CONFIG.lock(|unlocked| {
+            unlocked.borrow().map(|config| CorrectionSettings {
+                correction: config.correction(),
+                step_interval: config.step_interval(),
+            })
+        }),
+        stall: STALL_DETECTION_CONFIG.lock(|unlocked| {
+            unlocked.borrow().map(|config| StallSettings {
+                stall: config.stall(),
+                trigger_reason: config.trigger_reason(),
+            })
+        }),
+        estimator: ESTIMATOR_GAINS.lock(|unlocked| *unlocked.borrow()),
+        tracking_max_velocity: TRACKING_MAX_VELOCITY.lock(|unlocked| *unlocked.borrow()),
+        health: HEALTH_CHECK_CONFIG.lock(|unlocked| {
+            let config = unlocked.borrow();
+            Some(HealthSettings {
+                interval: config.interval(),
+                limits: config.limits(),
+            })
+        }),
+    }
+}
+
+// Settings acting on a stepper are only applied when `oid` is given
+fn apply(settings: &Settings, oid: Option<u8>) {
+    ENCODER_GEOMETRY.lock(|unlocked| *unlocked.borrow_mut() = settings.geometry);
+    CALIBRATION_TABLE.lock(|unlocked| *unlocked.borrow_mut() = settings.calibration.clone());
+    ESTIMATOR_GAINS.lock(|unlocked| *unlocked.borrow_mut() = settings.estimator);
+    TRACKING_MAX_VELOCITY.lock(|unlocked| *unlocked.borrow_mut() = settings.tracking_max_velocity);
+    if let Some(health) = settings.health {
+        HEALTH_CHECK_CONFIG.lock(|unlocked| {
+            *unlocked.borrow_mut() = HealthCheckConfig::new(health.interval, health.limits)
+        });
+    }
+
+    let Some(oid) = oid else {
+        return;
+    };
+    CLOSED_LOOP_CONFIG.lock(|unlocked| {
+        *unlocked.borrow_mut() = settings.correction.map(|correction| {
+            ClosedLoopConfig::new(oid, correction.correction, correction.step_interval)
+        })
+    });
+    STALL_DETECTION_CONFIG.lock(|unlocked| {
+        *unlocked.borrow_mut() = settings
+            .stall
+            .map(|stall| StallDetectionConfig::new(oid, stall.stall, stall.trigger_reason))
+    });
+}
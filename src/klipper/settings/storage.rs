@@ -0,0 +1,40 @@
+use closed_loop::settings::{self, Settings, MAX_SETTINGS_SIZE};
+use embedded_storage::{nor_flash::NorFlash, ReadStorage, Storage};
+use esp_storage::{FlashStorage, FlashStorageError};
+
+// Start of the `settings` partition, see partitions.csv
+const SETTINGS_OFFSET: u32 = 0x3F_0000;
+const SECTOR_SIZE: u32 = 0x1000;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Flash(FlashStorageError),
+    Settings(settings::Error),
+}
+
+pub fn read() -> Result<Settings, StorageError> {
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    FlashStorage::new()
+        .read(SETTINGS_OFFSET, &mut buffer)
+        .map_err(StorageError::Flash)?;
+    Settings::decode(&buffer).map_err(StorageError::Settings)
+}
+
+// Returns the number of bytes written
+pub fn write(settings: &Settings) -> Result<usize, StorageError> {
+    let mut buffer = [0u8; MAX_SETTINGS_SIZE];
+    let length = settings
+        .encode(&mut buffer)
+        .map_err(StorageError::Settings)?;
+    // Erases the sectors it writes to
+    FlashStorage::new()
+        .write(SETTINGS_OFFSET, &buffer[..length])
+        .map_err(StorageError::Flash)?;
+    Ok(length)
+}
+
+pub fn erase() -> Result<(), StorageError> {
+    FlashStorage::new()
+        .erase(SETTINGS_OFFSET, SETTINGS_OFFSET + SECTOR_SIZE)
+        .map_err(StorageError::Flash)
+}
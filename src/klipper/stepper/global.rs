@@ -7,7 +7,7 @@ use embassy_sync::{
     signal::Signal,
 };
 
-use super::StepperMessage;
+use super::{StepperCorrection, StepperMessage};
 
 // I don't love this idea, we may be able to move it to the `State` using a NoopRawMutex
 pub static STEPPER_MOVE_QUEUE: Channel<
@@ -25,4 +25,13 @@ pub static STEPPER_MOVE_QUEUE: Channel<
 
 pub static STEPPER_POSITION: Mutex<CriticalSectionRawMutex, RefCell<i32>> =
     Mutex::new(RefCell::new(0));
+// Set while the step driver goes through a step group from the move queue
+pub static STEPPER_BUSY: Mutex<CriticalSectionRawMutex, RefCell<bool>> =
+    Mutex::new(RefCell::new(false));
+// Level last driven on the stepper enable pin, `None` until the host configures it
+pub static STEPPER_ENABLE_LEVEL: Mutex<CriticalSectionRawMutex, RefCell<Option<bool>>> =
+    Mutex::new(RefCell::new(None));
 pub static STEPPER_STOP: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+pub static STEPPER_CORRECTION: Signal<CriticalSectionRawMutex, StepperCorrection> = Signal::new();
+// Steps the last correction put out, fewer than asked for when a stop or a step group cut it short
+pub static STEPPER_CORRECTED: Signal<CriticalSectionRawMutex, i32> = Signal::new();
@@ -37,3 +37,24 @@ impl core::cmp::Ord for StepperMessage {
         self.get_priority().cmp(&other.get_priority())
     }
 }
+
+// Steps requested by the closed loop, these are not part of the host's commanded position
+#[derive(Clone, Copy)]
+pub struct StepperCorrection {
+    steps: i32,
+    interval: u32,
+}
+
+impl StepperCorrection {
+    pub fn new(steps: i32, interval: u32) -> Self {
+        Self { steps, interval }
+    }
+
+    pub fn steps(&self) -> i32 {
+        self.steps
+    }
+
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+}
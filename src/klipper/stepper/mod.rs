@@ -11,7 +11,7 @@ mod step_info;
 mod task;
 
 pub use global::*;
-pub use message::StepperMessage;
+pub use message::{StepperCorrection, StepperMessage};
 pub use step_info::StepInfo;
 use task::step_driver;
 
@@ -78,15 +78,15 @@ pub fn config_stepper(
 ) {
     log::trace!("[ANCHOR] Config Stepper - oid: {oid}, step_pin: {step_pin}, dir_pin: {dir_pin}, invert_step: {invert_step}, step_pulse_ticks: {step_pulse_ticks}");
 
+    // The step pin idles at the `invert_step` level, the host sets the direction before stepping
     let mut step = context.step.take().unwrap().into_push_pull_output();
-    step.set_low().unwrap();
-    let mut dir = context.dir.take().unwrap().into_push_pull_output();
-
     if invert_step != 0 {
-        dir.set_high().unwrap();
+        step.set_high().unwrap();
     } else {
-        dir.set_low().unwrap();
+        step.set_low().unwrap();
     }
+    let mut dir = context.dir.take().unwrap().into_push_pull_output();
+    dir.set_low().unwrap();
 
     #[cfg(feature = "rmt_step")]
     {
@@ -110,7 +110,7 @@ pub fn config_stepper(
                 // step,
                 channel,
                 dir,
-                if invert_step == 0 { true } else { false },
+                invert_step != 0,
                 step_pulse_ticks,
                 STEPPER_MOVE_QUEUE.receiver(),
             ))
@@ -125,7 +125,7 @@ pub fn config_stepper(
             .spawn(step_driver(
                 step,
                 dir,
-                if invert_step == 0 { true } else { false },
+                invert_step != 0,
                 step_pulse_ticks,
                 STEPPER_MOVE_QUEUE.receiver(),
             ))
@@ -1,4 +1,5 @@
 use anchor::*;
+use embassy_futures::select::{select, Either};
 use embassy_sync::{self, blocking_mutex::raw::CriticalSectionRawMutex, channel::Receiver};
 
 use embassy_time::block_for;
@@ -10,9 +11,12 @@ use esp32c6_hal::{
     rmt::PulseCode,
 };
 
-use crate::TRIGGER_MAGNET_READ;
+use crate::klipper::encoder::TRIGGER_MAGNET_READ;
 
-use super::{StepperMessage, STEPPER_POSITION, STEPPER_STOP};
+use super::{
+    StepperMessage, STEPPER_BUSY, STEPPER_CORRECTED, STEPPER_CORRECTION, STEPPER_POSITION,
+    STEPPER_STOP,
+};
 
 #[embassy_executor::task]
 pub async fn step_driver(
@@ -53,8 +57,63 @@ pub async fn step_driver(
         }
     };
 
+    // Step group that came in during a correction, it goes out before anything else
+    let mut pending = None;
+
     loop {
-        let step_info = step_queue.receive().await;
+        let next = match pending.take() {
+            Some(step_info) => Either::First(step_info),
+            None => select(step_queue.receive(), STEPPER_CORRECTION.wait()).await,
+        };
+        let step_info = match next {
+            Either::First(step_info) => step_info,
+            Either::Second(correction) => {
+                // Corrections go out between step groups and leave the commanded position alone,
+                // they only bring the motor back to where the host thinks it is.
+                // The host's moves are scheduled ahead of time, the correction gives way as soon as
+                // one comes in so it can't push the move into the past.
+                let restore_dir = dir.is_set_high().unwrap();
+                if correction.steps().is_positive() {
+                    dir.set_high().unwrap();
+                } else {
+                    dir.set_low().unwrap();
+                }
+
+                let correction_interval = Duration::from_ticks(correction.interval() as u64);
+                let mut corrected = 0i32;
+                for _ in 0..correction.steps().unsigned_abs() {
+                    // Left signaled for the step groups, the move it stops may still be queued
+                    if STEPPER_STOP.signaled() {
+                        break;
+                    }
+
+                    #[cfg(not(feature = "rmt_step"))]
+                    step_pulse(&mut step, invert_step, pulse_duration);
+                    #[cfg(feature = "rmt_step")]
+                    {
+                        step = step.transmit(&[pulse]).wait().unwrap();
+                    }
+                    corrected += correction.steps().signum();
+
+                    if let Either::Second(step_info) =
+                        select(Timer::after(correction_interval), step_queue.receive()).await
+                    {
+                        pending = Some(step_info);
+                        break;
+                    }
+                }
+
+                if restore_dir {
+                    dir.set_high().unwrap();
+                } else {
+                    dir.set_low().unwrap();
+                }
+
+                STEPPER_CORRECTED.signal(corrected);
+                TRIGGER_MAGNET_READ.signal(());
+                continue;
+            }
+        };
         // if let Some(step_info) = step_queue.receive().await {
         match step_info {
             StepperMessage::StepInfo { _inner: step_info } => {
@@ -74,6 +133,10 @@ pub async fn step_driver(
                     continue;
                 }
 
+                STEPPER_BUSY.lock(|unlocked| {
+                    *unlocked.borrow_mut() = true;
+                });
+
                 let mut delay_between_pulses = Duration::from_ticks(step_info.interval() as u64);
 
                 for _ in 0..step_info.count() {
@@ -102,17 +165,7 @@ pub async fn step_driver(
 
                         // Step Pulse
                         #[cfg(not(feature = "rmt_step"))]
-                        if invert_step {
-                            step.set_low().unwrap();
-                            // Timer::after(pulse_duration).await;
-                            block_for(pulse_duration);
-                            step.set_high().unwrap();
-                        } else {
-                            step.set_high().unwrap();
-                            // Timer::after(pulse_duration).await;
-                            block_for(pulse_duration);
-                            step.set_low().unwrap();
-                        };
+                        step_pulse(&mut step, invert_step, pulse_duration);
                         #[cfg(feature = "rmt_step")]
                         {
                             step = step.transmit(&[pulse]).wait().unwrap();
@@ -120,6 +173,16 @@ pub async fn step_driver(
 
                         step_clock = Instant::now();
 
+                        // Kept up to date within the group, the encoder reads while it goes out
+                        if step_info.dir() {
+                            step_counter = step_counter.wrapping_add(1);
+                        } else {
+                            step_counter = step_counter.wrapping_sub(1);
+                        }
+                        STEPPER_POSITION.lock(|unlocked| {
+                            *unlocked.borrow_mut() = step_counter;
+                        });
+
                         if step_info.add() != 0 {
                             if step_info.add().is_positive() {
                                 delay_between_pulses = delay_between_pulses
@@ -136,18 +199,9 @@ pub async fn step_driver(
 
                 TRIGGER_MAGNET_READ.signal(());
 
-                // Step counter
-                if step_info.dir() {
-                    step_counter = step_counter.wrapping_add(step_info.count() as i32);
-                    STEPPER_POSITION.lock(|unlocked| {
-                        *unlocked.borrow_mut() = step_counter;
-                    });
-                } else {
-                    step_counter = step_counter.wrapping_sub(step_info.count() as i32);
-                    STEPPER_POSITION.lock(|unlocked| {
-                        *unlocked.borrow_mut() = step_counter;
-                    });
-                }
+                STEPPER_BUSY.lock(|unlocked| {
+                    *unlocked.borrow_mut() = false;
+                });
             }
 
             StepperMessage::ResetStepClock => {
@@ -156,3 +210,25 @@ pub async fn step_driver(
         }
     }
 }
+
+#[cfg(not(feature = "rmt_step"))]
+fn step_pulse(
+    step: &mut esp32c6_hal::gpio::GpioPin<
+        esp32c6_hal::gpio::Output<esp32c6_hal::gpio::PushPull>,
+        5,
+    >,
+    invert_step: bool,
+    pulse_duration: Duration,
+) {
+    if invert_step {
+        step.set_low().unwrap();
+        // Timer::after(pulse_duration).await;
+        block_for(pulse_duration);
+        step.set_high().unwrap();
+    } else {
+        step.set_high().unwrap();
+        // Timer::after(pulse_duration).await;
+        block_for(pulse_duration);
+        step.set_low().unwrap();
+    };
+}
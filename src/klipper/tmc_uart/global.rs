@@ -0,0 +1,26 @@
+use core::cell::RefCell;
+
+use closed_loop::current::IholdIrun;
+use embassy_sync::{
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex},
+    signal::Signal,
+};
+use embassy_time::Instant;
+use esp32c6_hal::{peripherals::UART1, Uart};
+
+pub type TMCSerial = Mutex<CriticalSectionRawMutex, RefCell<Option<Uart<'static, UART1>>>>;
+
+// Shared between the host's `tmcuart_send` and the MCU's own register writes. A transfer takes the
+// UART out for its length, see `with_serial`.
+pub static TMC_SERIAL: TMCSerial = Mutex::new(RefCell::new(None));
+
+// Slave address and `IHOLD_IRUN` value of the host's latest current write
+pub static HOST_IHOLD_IRUN: Signal<CriticalSectionRawMutex, (u8, IholdIrun)> = Signal::new();
+
+// Register writes the MCU made to each driver address, they are taken off the host's `IFCNT` reads
+// so its own writes still count up by one
+pub static MCU_WRITES: Mutex<CriticalSectionRawMutex, RefCell<[u8; 4]>> =
+    Mutex::new(RefCell::new([0; 4]));
+
+pub static LAST_HOST_TRANSFER: Mutex<CriticalSectionRawMutex, RefCell<Instant>> =
+    Mutex::new(RefCell::new(Instant::from_ticks(0)));
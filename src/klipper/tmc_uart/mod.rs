@@ -1,10 +1,19 @@
 use anchor::*;
+use closed_loop::current::{
+    hide_writes, parse_read_request, parse_write_datagram, write_datagram, IholdIrun, IFCNT,
+    IHOLD_IRUN,
+};
+use embassy_time::Instant;
+use embedded_io::{Read, Write};
+use esp32c6_hal::{peripherals::UART1, Uart};
 use heapless::Entry;
 
 use crate::klipper::oid_types::*;
 use crate::State;
 
+mod global;
 mod helper;
+pub use global::*;
 use helper::{clad_uart_protocol_bits, strip_uart_protocol_bits};
 
 #[klipper_command]
@@ -19,8 +28,13 @@ pub fn config_tmcuart(
 ) {
     log::trace!("[ANCHOR] Config TMC UART - oid: {oid}, rx_pin: {rx_pin}, pull_up: {pull_up}, tx_pin: {tx_pin}, bit_time: {bit_time}");
 
+    // Reconfiguring keeps the UART that was handed over the first time
+    if let Some(tmc_serial) = context.tmc_serial.take() {
+        TMC_SERIAL.lock(|unlocked| *unlocked.borrow_mut() = Some(tmc_serial));
+    }
+
     let tmc_uart = TMCUart::new(
-        context.tmc_serial.take().unwrap(),
+        &TMC_SERIAL,
         if pull_up == 1 { true } else { false },
         bit_time,
     );
@@ -45,6 +59,13 @@ pub fn tmcuart_send(context: &mut State, oid: u8, write: &[u8], read: u8) {
     let mut uart_bytes = [0; 8];
     let corrected_length = strip_uart_protocol_bits(&mut uart_bytes, write);
 
+    LAST_HOST_TRANSFER.lock(|unlocked| *unlocked.borrow_mut() = Instant::now());
+    if let Some((slave, IHOLD_IRUN, value)) = parse_write_datagram(&uart_bytes[..corrected_length])
+    {
+        // The current requested by the host is what the MCU's own current policy scales from
+        HOST_IHOLD_IRUN.signal((slave, IholdIrun::from_bits(value)));
+    }
+
     // log::trace!(
     //     "[ANCHOR] TMC UART Send - real_bytes : {:X?}",
     //     &uart_bytes[0..corrected_length]
@@ -54,21 +75,29 @@ pub fn tmcuart_send(context: &mut State, oid: u8, write: &[u8], read: u8) {
     let mut corrected_read_buffer = [0; 128];
     match context.oids.get_mut(&oid).unwrap() {
         OIDTypes::TMCUart { _inner } => {
-            _inner.send(&uart_bytes[..corrected_length]).unwrap();
-            // Burn our own sent bytes from the buffer, we don't want those.
-            _inner
-                .read_exact(&mut read_buffer[..corrected_length])
-                .unwrap();
+            if read != 0 && read != 10 {
+                unimplemented!("Haven't implemented any way to do reads of anything other than 10");
+            }
+            let response_length = if read != 0 { 8 } else { 0 };
 
-            if read != 0 {
-                if read != 10 {
-                    unimplemented!(
-                        "Haven't implemented any way to do reads of anything other than 10"
-                    );
-                }
+            let Some(result) = _inner.transfer(
+                &uart_bytes[..corrected_length],
+                &mut read_buffer[..response_length],
+            ) else {
+                // Nothing came back as far as the host can tell, it retries the transfer
+                log::warn!("TMC UART in use by the MCU, dropping the host's transfer");
+                klipper_reply!(tmcuart_response, oid: u8 = oid, read: &[u8] = &[]);
+                return;
+            };
+            result.unwrap();
 
-                _inner.read_exact(&mut read_buffer[0..8 as usize]).unwrap();
+            if let Some((slave, IFCNT)) = parse_read_request(&uart_bytes[..corrected_length]) {
+                let writes = MCU_WRITES
+                    .lock(|unlocked| unlocked.borrow().get(slave as usize).copied().unwrap_or(0));
+                hide_writes(&mut read_buffer[..response_length], writes);
+            }
 
+            if read != 0 {
                 // log::trace!(
                 //     "Read bytes : {read_bytes}, Data - {:X?}",
                 //     &read_buffer[0..read_bytes]
@@ -92,3 +121,39 @@ pub fn tmcuart_send(context: &mut State, oid: u8, write: &[u8], read: u8) {
         _ => panic!("Expected OID to be a TMCUart, but it wasn't!"),
     }
 }
+
+// Takes the UART out of `serial` for the length of `f`, so no critical section is held over the
+// transfer. `None` while the other side is using it or before the host configured it.
+pub fn with_serial<R>(
+    serial: &TMCSerial,
+    f: impl FnOnce(&mut Uart<'static, UART1>) -> R,
+) -> Option<R> {
+    let mut uart = serial.lock(|unlocked| unlocked.borrow_mut().take())?;
+    let result = f(&mut uart);
+    serial.lock(|unlocked| *unlocked.borrow_mut() = Some(uart));
+    Some(result)
+}
+
+// Writes a driver register from the MCU. Returns false if the UART is in use or the host has not
+// configured it yet.
+pub fn write_register(slave: u8, register: u8, value: u32) -> bool {
+    let written = with_serial(&TMC_SERIAL, |uart| {
+        let datagram = write_datagram(slave, register, value);
+        if uart.write_all(&datagram).is_err() {
+            return false;
+        }
+        // Single wire UART, burn our own sent bytes
+        let mut echo = [0; 8];
+        uart.read_exact(&mut echo).is_ok()
+    })
+    .unwrap_or(false);
+
+    if written {
+        MCU_WRITES.lock(|unlocked| {
+            if let Some(writes) = unlocked.borrow_mut().get_mut(slave as usize) {
+                *writes = writes.wrapping_add(1);
+            }
+        });
+    }
+    written
+}
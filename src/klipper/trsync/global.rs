@@ -1,6 +1,15 @@
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+use core::cell::RefCell;
+
+use embassy_sync::{
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex},
+    channel::Channel,
+};
 
 use super::TRSyncMessage;
 
 //TODO: Probably a better solution here is a static_cell/lazy_static of a hashmap that contains TRSYNC channels for a few, for the time being this fine
 pub static TRSYNC_CHANNEL: Channel<CriticalSectionRawMutex, TRSyncMessage, 8> = Channel::new();
+// Set while a trsync runner is around to pick up triggers, outside of homing and probing moves
+// nothing drains the channel
+pub static TRSYNC_ACTIVE: Mutex<CriticalSectionRawMutex, RefCell<bool>> =
+    Mutex::new(RefCell::new(false));
@@ -7,7 +7,7 @@ use embassy_time::{Duration, Instant, Timer};
 
 use crate::klipper::stepper::STEPPER_STOP;
 
-use super::{trsync_report, TRSyncMessage, TRSYNC_CHANNEL};
+use super::{trsync_report, TRSyncMessage, TRSYNC_ACTIVE, TRSYNC_CHANNEL};
 
 #[embassy_executor::task]
 pub async fn trsync_runner(oid: u8, report_clock: u32, report_ticks: u32, expire_reason: u8) {
@@ -18,6 +18,7 @@ pub async fn trsync_runner(oid: u8, report_clock: u32, report_ticks: u32, expire
         .unwrap();
     let mut expire_reason = expire_reason;
     let mut end = false;
+    TRSYNC_ACTIVE.lock(|unlocked| *unlocked.borrow_mut() = true);
 
     loop {
         match select(Timer::at(next_report), TRSYNC_CHANNEL.receive()).await {
@@ -46,6 +47,7 @@ pub async fn trsync_runner(oid: u8, report_clock: u32, report_ticks: u32, expire
                     .unwrap();
 
                 if end {
+                    TRSYNC_ACTIVE.lock(|unlocked| *unlocked.borrow_mut() = false);
                     return;
                 }
             }
@@ -3,9 +3,8 @@
 #![feature(type_alias_impl_trait)]
 
 use anchor::{klipper_config_generate, SliceInputBuffer};
-use as5600_async::{status::Status, As5600};
+use as5600_async::As5600;
 use embassy_executor::{Executor, Spawner};
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
 use embassy_time::{Duration, Timer};
 use embedded_io::Write;
 use embedded_io_async::{Read as AsyncRead, Write as AsyncWrite};
@@ -14,7 +13,7 @@ use esp32c6_hal::{
     embassy, entry,
     gpio::{GpioPin, Unknown, IO},
     i2c::I2C,
-    peripherals::{Peripherals, I2C0, UART1},
+    peripherals::{Peripherals, UART1},
     prelude::*,
     rmt::Channel,
     system::SystemExt,
@@ -23,7 +22,7 @@ use esp32c6_hal::{
     Rmt, Uart, UsbSerialJtag,
 };
 use esp_backtrace as _;
-use klipper::{stepper::STEPPER_POSITION, USB_MAX_PACKET_SIZE, USB_READY_TO_SEND};
+use klipper::{USB_MAX_PACKET_SIZE, USB_READY_TO_SEND};
 use smart_leds::RGB8;
 use smart_leds_trait::SmartLedsWrite;
 use static_cell::StaticCell;
@@ -96,6 +95,7 @@ fn main() -> ! {
     );
 
     let as5600_driver = As5600::new(i2c);
+    klipper::settings::restore();
     // let usb_serial = UsbSerialJtag::new(peripherals.USB_DEVICE);
     let (usb_tx, usb_rx) = UsbSerialJtag::new(peripherals.USB_DEVICE).split();
 
@@ -103,7 +103,9 @@ fn main() -> ! {
     executor.run(|spawner| {
         // spawner.spawn(onboard_rgb_led(ws_driver)).ok();
         log::debug!("AS5600 Task");
-        spawner.spawn(as5600_task(as5600_driver)).ok();
+        spawner
+            .spawn(klipper::encoder::as5600_task(as5600_driver))
+            .ok();
         log::debug!("USB Writer");
         spawner.spawn(usb_writer(usb_tx)).ok();
         log::debug!("USB Reader");
@@ -185,47 +187,6 @@ async fn usb_writer(mut usb_tx: UsbSerialJtagTx<'static>) {
     }
 }
 
-pub static TRIGGER_MAGNET_READ: Signal<CriticalSectionRawMutex, ()> = Signal::new();
-static MAGNET_SENSOR: Signal<CriticalSectionRawMutex, u16> = Signal::new();
-pub static CALIBRATION_ANGLE: Signal<CriticalSectionRawMutex, u16> = Signal::new();
-
-#[embassy_executor::task]
-async fn as5600_task(mut driver: As5600<I2C<'static, I2C0>>) {
-    loop {
-        match driver.magnet_status().await {
-            Ok(state) => match state {
-                Status::MagnetDetected => {
-                    log::info!("Magnet detected");
-                    break;
-                }
-                _ => {
-                    log::error!("Magnet not detected, or detected with error - {state:?}")
-                }
-            },
-            Err(e) => {
-                log::error!("Error with magnet detection occured : {:?}", e);
-            }
-        }
-    }
-    const DEG_PER_TICK: f32 = 360. / 4096.;
-    const DEG_PER_STEP: f32 = 360. / 3200.;
-    let start_angle = driver.angle().await.unwrap();
-
-    // This needs work for calcs
-    loop {
-        TRIGGER_MAGNET_READ.wait().await;
-        TRIGGER_MAGNET_READ.reset();
-        let angle = driver.angle().await.unwrap();
-        let pos = STEPPER_POSITION.lock(|unlocked| *unlocked.borrow()).abs() % 3200;
-        log::info!(
-            "Magnet sensor reading : {} | pos : {}",
-            (angle.abs_diff(start_angle)) as f32 * DEG_PER_TICK,
-            pos as f32 * DEG_PER_STEP,
-        );
-        MAGNET_SENSOR.signal(angle);
-    }
-}
-
 #[embassy_executor::task]
 async fn onboard_rgb_led(
     mut rgb_driver: ws2812_driver::SmartLedsAdapter<esp32c6_hal::rmt::Channel<0>, 25>,